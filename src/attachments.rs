@@ -0,0 +1,337 @@
+use crate::{error::FfmpegError, utils::from_path, OutputFormat, ThumbnailerError};
+
+use ffmpeg_sys_next::{
+    av_dict_get, avformat_close_input, avformat_open_input, AVCodecID, AVFormatContext,
+    AVMediaType, AVStream, AV_DISPOSITION_ATTACHED_PIC,
+};
+use std::{
+    ffi::{CStr, CString},
+    path::Path,
+};
+
+/// File attached to a container, such as the cover art of a Matroska file, which FFmpeg exposes
+/// as an attachment stream rather than as a video stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// Index of the attachment stream, to be handed to `Thumbnailer::extract_attachment`
+    pub index: usize,
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
+    pub size: usize,
+}
+
+/// Kind of an embedded picture, from the picture type of ID3 and FLAC tags that FFmpeg exposes as
+/// the `comment` of its stream, or from the Matroska cover art file names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArtType {
+    FrontCover,
+    BackCover,
+    /// Picture of the medium, such as the label side of a CD
+    Media,
+    /// Lead artist, performer or band
+    Artist,
+    /// Any other picture type
+    Other,
+}
+
+impl ArtType {
+    fn from_comment(comment: &str) -> Self {
+        match comment {
+            "Cover (front)" => ArtType::FrontCover,
+            "Cover (back)" => ArtType::BackCover,
+            "Media (e.g. label side of CD)" => ArtType::Media,
+            "Lead artist/lead performer/soloist" | "Artist/performer" | "Band/Orchestra" => {
+                ArtType::Artist
+            }
+            _ => ArtType::Other,
+        }
+    }
+}
+
+/// Picture embedded in a container as a video stream, such as the cover art of an MP3 or MP4
+/// file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedPicture {
+    /// Index of the stream of the picture
+    pub index: usize,
+    /// Kind of the picture, if tagged with one
+    pub art_type: Option<ArtType>,
+    pub comment: Option<String>,
+    pub filename: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// Input opened only to read its headers, attachments being fully described there
+pub(crate) struct InputFile {
+    pub(crate) format_context: *mut AVFormatContext,
+}
+
+impl InputFile {
+    pub(crate) fn open(path: &Path) -> Result<Self, ThumbnailerError> {
+        let path = from_path(path)?;
+        let mut format_context = std::ptr::null_mut();
+        match unsafe {
+            avformat_open_input(
+                &mut format_context,
+                path.as_ptr(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        } {
+            0 => Ok(Self { format_context }),
+            e => Err(ThumbnailerError::FfmpegWithReason(
+                FfmpegError::from(e),
+                "Failed to open input".to_string(),
+            )),
+        }
+    }
+
+    /// Streams of the input whose codec parameters are of `media_type`
+    pub(crate) fn streams_of_type(
+        &self,
+        media_type: AVMediaType,
+    ) -> impl Iterator<Item = *mut AVStream> + '_ {
+        (0..unsafe { (*self.format_context).nb_streams } as usize)
+            .map(|index| unsafe { *(*self.format_context).streams.add(index) })
+            .filter(move |&stream| unsafe { (*(*stream).codecpar).codec_type } == media_type)
+    }
+
+    fn attachment_streams(&self) -> impl Iterator<Item = *mut AVStream> + '_ {
+        self.streams_of_type(AVMediaType::AVMEDIA_TYPE_ATTACHMENT)
+    }
+}
+
+impl Drop for InputFile {
+    fn drop(&mut self) {
+        unsafe { avformat_close_input(&mut self.format_context) };
+    }
+}
+
+pub(crate) fn list_attachments(path: &Path) -> Result<Vec<Attachment>, ThumbnailerError> {
+    let input = InputFile::open(path)?;
+    let attachments = input
+        .attachment_streams()
+        .map(|stream| unsafe {
+            Attachment {
+                index: (*stream).index as usize,
+                filename: stream_tag(stream, "filename"),
+                mime_type: stream_tag(stream, "mimetype"),
+                size: (*(*stream).codecpar).extradata_size as usize,
+            }
+        })
+        .collect();
+    Ok(attachments)
+}
+
+pub(crate) fn extract_attachment(path: &Path, index: usize) -> Result<Vec<u8>, ThumbnailerError> {
+    let input = InputFile::open(path)?;
+    let stream = input
+        .attachment_streams()
+        .find(|&stream| unsafe { (*stream).index } as usize == index)
+        .ok_or(ThumbnailerError::AttachmentNotFound(index))?;
+
+    // Attachments are stored whole in the extra data of their stream
+    let codec_parameters = unsafe { &*(*stream).codecpar };
+    if codec_parameters.extradata.is_null() {
+        return Ok(Vec::new());
+    }
+    Ok(unsafe {
+        std::slice::from_raw_parts(
+            codec_parameters.extradata,
+            codec_parameters.extradata_size as usize,
+        )
+    }
+    .to_vec())
+}
+
+/// Encoded bytes of the first embedded picture, such as the cover art of an MP4 or Matroska
+/// file, along with their format, if any of a format enabled in this build
+pub(crate) fn extract_embedded_raw(
+    path: &Path,
+) -> Result<Option<(OutputFormat, Vec<u8>)>, ThumbnailerError> {
+    let input = InputFile::open(path)?;
+    let embedded = input
+        .streams_of_type(AVMediaType::AVMEDIA_TYPE_VIDEO)
+        .filter(|&stream| unsafe { (*stream).disposition } & AV_DISPOSITION_ATTACHED_PIC != 0)
+        .find_map(|stream| {
+            let format = embedded_format(unsafe { (*(*stream).codecpar).codec_id })?;
+            // Embedded pictures are read whole along with the headers
+            let packet = unsafe { &(*stream).attached_pic };
+            if packet.data.is_null() || packet.size <= 0 {
+                return None;
+            }
+            let bytes = unsafe { std::slice::from_raw_parts(packet.data, packet.size as usize) };
+            Some((format, bytes.to_vec()))
+        });
+    Ok(embedded)
+}
+
+/// Width and height of the first embedded picture, such as the cover art of an MP4 or Matroska
+/// file, read from the container or else from the header of the picture, without decoding it
+pub(crate) fn embedded_picture_dimensions(
+    path: &Path,
+) -> Result<Option<(u32, u32)>, ThumbnailerError> {
+    let input = InputFile::open(path)?;
+    let dimensions = input
+        .streams_of_type(AVMediaType::AVMEDIA_TYPE_VIDEO)
+        .find(|&stream| unsafe { (*stream).disposition } & AV_DISPOSITION_ATTACHED_PIC != 0)
+        .and_then(|stream| unsafe { picture_dimensions(stream) });
+    Ok(dimensions)
+}
+
+pub(crate) fn list_embedded_pictures(
+    path: &Path,
+) -> Result<Vec<EmbeddedPicture>, ThumbnailerError> {
+    let input = InputFile::open(path)?;
+    let pictures = input
+        .streams_of_type(AVMediaType::AVMEDIA_TYPE_VIDEO)
+        .filter(|&stream| unsafe { (*stream).disposition } & AV_DISPOSITION_ATTACHED_PIC != 0)
+        .map(|stream| unsafe {
+            let dimensions = picture_dimensions(stream);
+            EmbeddedPicture {
+                index: (*stream).index as usize,
+                art_type: stream_art_type(stream),
+                comment: stream_tag(stream, "comment"),
+                filename: stream_tag(stream, "filename"),
+                width: dimensions.map(|(width, _)| width),
+                height: dimensions.map(|(_, height)| height),
+            }
+        })
+        .collect();
+    Ok(pictures)
+}
+
+/// Kind of the embedded picture of `stream`, from its `comment` or its Matroska file name
+pub(crate) unsafe fn stream_art_type(stream: *const AVStream) -> Option<ArtType> {
+    if let Some(comment) = stream_tag(stream, "comment") {
+        return Some(ArtType::from_comment(&comment));
+    }
+    let filename = stream_tag(stream, "filename")?;
+    // Matroska names its front covers cover, cover_land, small_cover and small_cover_land
+    let is_front_cover = filename.starts_with("cover") || filename.starts_with("small_cover");
+    Some(if is_front_cover {
+        ArtType::FrontCover
+    } else {
+        ArtType::Other
+    })
+}
+
+unsafe fn picture_dimensions(stream: *const AVStream) -> Option<(u32, u32)> {
+    let codec_parameters = &*(*stream).codecpar;
+    if codec_parameters.width > 0 && codec_parameters.height > 0 {
+        return Some((
+            codec_parameters.width as u32,
+            codec_parameters.height as u32,
+        ));
+    }
+    let packet = &(*stream).attached_pic;
+    if packet.data.is_null() || packet.size <= 0 {
+        return None;
+    }
+    image_dimensions(std::slice::from_raw_parts(
+        packet.data,
+        packet.size as usize,
+    ))
+}
+
+/// Width and height of a PNG, JPEG or WebP image, read from its header
+fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let u16_be = |at: usize| Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let u32_be = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let u32_le = |at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // The IHDR chunk always comes first
+        return Some((u32_be(16)?, u32_be(20)?));
+    }
+
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(&b"WEBP"[..]) {
+        return match bytes.get(12..16)? {
+            b"VP8 " => Some((
+                u16::from_le_bytes(bytes.get(26..28)?.try_into().ok()?) as u32 & 0x3fff,
+                u16::from_le_bytes(bytes.get(28..30)?.try_into().ok()?) as u32 & 0x3fff,
+            )),
+            // 14 bits each, following a signature byte, minus one
+            b"VP8L" => {
+                let bits = u32_le(21)?;
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            // 24 bits each, minus one
+            b"VP8X" => Some(((u32_le(24)? & 0xff_ffff) + 1, (u32_le(27)? & 0xff_ffff) + 1)),
+            _ => None,
+        };
+    }
+
+    if bytes.starts_with(&[0xff, 0xd8]) {
+        // Segments are skipped until the start of frame one, giving the dimensions
+        let mut at = 2;
+        while *bytes.get(at)? == 0xff {
+            let marker = *bytes.get(at + 1)?;
+            let is_start_of_frame =
+                (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker);
+            if is_start_of_frame {
+                return Some((u16_be(at + 7)? as u32, u16_be(at + 5)? as u32));
+            }
+            at += 2 + u16_be(at + 2)? as usize;
+        }
+    }
+
+    None
+}
+
+fn embedded_format(codec_id: AVCodecID) -> Option<OutputFormat> {
+    match codec_id {
+        #[cfg(feature = "jpeg")]
+        AVCodecID::AV_CODEC_ID_MJPEG => Some(OutputFormat::Jpeg),
+        #[cfg(feature = "png")]
+        AVCodecID::AV_CODEC_ID_PNG => Some(OutputFormat::Png),
+        #[cfg(feature = "webp")]
+        AVCodecID::AV_CODEC_ID_WEBP => Some(OutputFormat::Webp),
+        _ => None,
+    }
+}
+
+unsafe fn stream_tag(stream: *const AVStream, key: &str) -> Option<String> {
+    let key = CString::new(key).unwrap();
+    let tag = av_dict_get((*stream).metadata, key.as_ptr(), std::ptr::null(), 0);
+    if tag.is_null() {
+        return None;
+    }
+    Some(CStr::from_ptr((*tag).value).to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn art_type_from_id3_picture_type() {
+        assert_eq!(ArtType::from_comment("Cover (front)"), ArtType::FrontCover);
+        assert_eq!(ArtType::from_comment("Artist/performer"), ArtType::Artist);
+        assert_eq!(
+            ArtType::from_comment("A bright coloured fish"),
+            ArtType::Other
+        );
+    }
+
+    #[test]
+    fn reads_png_dimensions() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&640u32.to_be_bytes());
+        png.extend_from_slice(&360u32.to_be_bytes());
+        assert_eq!(image_dimensions(&png), Some((640, 360)));
+    }
+
+    #[test]
+    fn reads_jpeg_dimensions_past_other_segments() {
+        let jpeg = [
+            0xff, 0xd8, // Start of image
+            0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, // APP0
+            0xff, 0xc4, 0x00, 0x02, // DHT, not a start of frame
+            0xff, 0xc2, 0x00, 0x08, 0x08, 0x01, 0x68, 0x02, 0x80, 0x03, // Progressive SOF
+        ];
+        assert_eq!(image_dimensions(&jpeg), Some((640, 360)));
+        assert_eq!(image_dimensions(&jpeg[..12]), None);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{ThumbnailerError, VideoFrame};
+
+/// Statistic summarizing the luminance of a frame's pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LuminanceStatistic {
+    /// Mean luminance, easily raised by a small bright area such as a logo
+    Mean,
+    /// Median luminance, ignoring small bright areas
+    Median,
+    /// Luminance below which the given percentage, in range [0.0, 100.0], of the pixels are
+    Percentile(f32),
+}
+
+impl LuminanceStatistic {
+    /// Evaluates the statistic over a histogram of the 256 luminance levels
+    fn evaluate(&self, histogram: &[u64; 256]) -> f32 {
+        let total: u64 = histogram.iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let percentile = match self {
+            LuminanceStatistic::Mean => {
+                let sum: u64 = histogram
+                    .iter()
+                    .enumerate()
+                    .map(|(level, count)| level as u64 * count)
+                    .sum();
+                return sum as f32 / total as f32;
+            }
+            LuminanceStatistic::Median => 50.0,
+            LuminanceStatistic::Percentile(percentile) => *percentile,
+        };
+
+        let rank = ((percentile as f64 / 100.0 * total as f64).ceil() as u64).max(1);
+        let mut cumulated = 0;
+        for (level, count) in histogram.iter().enumerate() {
+            cumulated += count;
+            if cumulated >= rank {
+                return level as f32;
+            }
+        }
+        255.0
+    }
+}
+
+/// Configuration of the blank frame detection, set with `ThumbnailerBuilder::skip_blank_frames`
+///
+/// A frame is blank when the `statistic` of its luminance, in range [0.0, 255.0], is below
+/// `threshold`. Blank frames are skipped by trying up to `max_attempts` later positions, each
+/// `step_percentage` of the video duration apart, the brightest frame being kept if all of them
+/// are blank.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlankFrameDetection {
+    pub threshold: f32,
+    pub statistic: LuminanceStatistic,
+    pub max_attempts: u32,
+    pub step_percentage: f32,
+}
+
+impl Default for BlankFrameDetection {
+    fn default() -> Self {
+        Self {
+            threshold: 24.0,
+            statistic: LuminanceStatistic::Mean,
+            max_attempts: 5,
+            step_percentage: 0.05,
+        }
+    }
+}
+
+impl BlankFrameDetection {
+    pub(crate) fn validate(&self) -> Result<(), ThumbnailerError> {
+        if !(0.0..=255.0).contains(&self.threshold) {
+            return Err(ThumbnailerError::InvalidLuminanceThreshold(self.threshold));
+        }
+        if let LuminanceStatistic::Percentile(percentile) = self.statistic {
+            if !(0.0..=100.0).contains(&percentile) {
+                return Err(ThumbnailerError::InvalidPercentile(percentile));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.step_percentage) {
+            return Err(ThumbnailerError::InvalidSeekPercentage(
+                self.step_percentage,
+            ));
+        }
+        Ok(())
+    }
+
+    /// The selected statistic of the frame luminance
+    pub(crate) fn luminance(&self, video_frame: &VideoFrame) -> f32 {
+        self.statistic.evaluate(&video_frame.luminance_histogram())
+    }
+
+    pub(crate) fn is_blank(&self, luminance: f32) -> bool {
+        luminance < self.threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logo_on_black() -> [u64; 256] {
+        // 90% black pixels and a 10% white logo
+        let mut histogram = [0; 256];
+        histogram[0] = 90;
+        histogram[255] = 10;
+        histogram
+    }
+
+    #[test]
+    fn mean_is_raised_by_a_bright_logo() {
+        assert_eq!(LuminanceStatistic::Mean.evaluate(&logo_on_black()), 25.5);
+    }
+
+    #[test]
+    fn median_ignores_a_bright_logo() {
+        assert_eq!(LuminanceStatistic::Median.evaluate(&logo_on_black()), 0.0);
+    }
+
+    #[test]
+    fn percentile_bounds() {
+        let histogram = logo_on_black();
+        assert_eq!(
+            LuminanceStatistic::Percentile(90.0).evaluate(&histogram),
+            0.0
+        );
+        assert_eq!(
+            LuminanceStatistic::Percentile(91.0).evaluate(&histogram),
+            255.0
+        );
+        assert_eq!(
+            LuminanceStatistic::Percentile(0.0).evaluate(&histogram),
+            0.0
+        );
+        assert_eq!(
+            LuminanceStatistic::Percentile(100.0).evaluate(&histogram),
+            255.0
+        );
+    }
+}
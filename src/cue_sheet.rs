@@ -0,0 +1,76 @@
+use crate::ThumbnailerError;
+
+use std::time::Duration;
+
+/// Parses a cue sheet listing one timestamp per line, as handed to
+/// `Thumbnailer::process_from_cuesheet`. Timestamps are either seconds, e.g. `83.5`, or
+/// `MM:SS` and `HH:MM:SS` with optional fractional seconds, e.g. `01:23.5`. Blank lines and
+/// lines starting with `#` are ignored
+pub fn parse_cue_sheet(cue_sheet: &str) -> Result<Vec<Duration>, ThumbnailerError> {
+    cue_sheet
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            parse_timestamp(line).ok_or_else(|| ThumbnailerError::InvalidCueSheet {
+                line: line_number,
+                content: line.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let mut fields = timestamp.rsplit(':');
+    let seconds = fields.next()?.parse::<f64>().ok()?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return None;
+    }
+
+    let mut total = seconds;
+    let mut last = seconds;
+    // Minutes then hours
+    for multiplier in [60.0, 3600.0] {
+        let field = match fields.next() {
+            Some(field) => field,
+            None => return Some(Duration::from_secs_f64(total)),
+        };
+        // Units followed by a larger one are below 60
+        if last >= 60.0 {
+            return None;
+        }
+        last = field.parse::<u32>().ok()? as f64;
+        total += last * multiplier;
+    }
+    if fields.next().is_some() {
+        return None;
+    }
+    Some(Duration::from_secs_f64(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_timestamp_format() {
+        let cue_sheet = "# Episode 1\n12.5\n\n01:02\n1:00:03.25\n";
+        assert_eq!(
+            parse_cue_sheet(cue_sheet).unwrap(),
+            vec![
+                Duration::from_millis(12_500),
+                Duration::from_secs(62),
+                Duration::from_millis(3_603_250),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_the_invalid_line() {
+        assert!(matches!(
+            parse_cue_sheet("10\n1:75\n"),
+            Err(ThumbnailerError::InvalidCueSheet { line: 2, .. })
+        ));
+    }
+}
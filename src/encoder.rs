@@ -0,0 +1,27 @@
+use crate::VideoFrame;
+
+use std::{error::Error, fmt, sync::Arc};
+
+/// Encoder replacing the built-in ones when set with `ThumbnailerBuilder::custom_encoder`, e.g.
+/// to output a format the crate doesn't support
+pub trait Encoder: Send + Sync {
+    /// Encodes the RGB24 pixels of `video_frame`, whose rows are `line_size` bytes apart, with the
+    /// `quality` set on the `ThumbnailerBuilder`, in range [0.0, 100.0], or its `raw_quality`
+    /// when set
+    fn encode(
+        &self,
+        video_frame: &VideoFrame,
+        quality: f32,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>>;
+}
+
+/// Shared handle on a custom `Encoder`, so that the `ThumbnailerBuilder` holding it stays
+/// cloneable
+#[derive(Clone)]
+pub(crate) struct CustomEncoder(pub(crate) Arc<dyn Encoder>);
+
+impl fmt::Debug for CustomEncoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomEncoder")
+    }
+}
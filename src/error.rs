@@ -1,5 +1,6 @@
 use std::ffi::c_int;
 use std::path::PathBuf;
+use std::time::Duration;
 use thiserror::Error;
 use tokio::task::JoinError;
 
@@ -14,12 +15,20 @@ use ffmpeg_sys_next::{
 };
 
 /// Error type for the library.
+///
+/// New variants may be added in any release, `ThumbnailerError::kind` gives a stable category to
+/// branch on instead.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum ThumbnailerError {
     #[error("I/O Error: {0}")]
     Io(#[from] std::io::Error),
     #[error("Path conversion error: Path: {0:#?}")]
     PathConversion(PathBuf),
+    #[error("The input is empty")]
+    EmptyInput,
+    #[error("Invalid timestamp on line {line} of the cue sheet: {content:?}")]
+    InvalidCueSheet { line: usize, content: String },
     #[error("FFMPEG internal error: {0}")]
     Ffmpeg(#[from] FfmpegError),
     #[error("FFMPEG internal error: {0}; Reason: {1}")]
@@ -28,19 +37,161 @@ pub enum ThumbnailerError {
     FrameDecodeError,
     #[error("Failed to seek video")]
     SeekError,
+    #[error("The input ends before its expected duration, it may be truncated")]
+    TruncatedFile,
+    #[error("Opening the input took longer than {0:?}")]
+    OpenTimeout(Duration),
     #[error("Seek not allowed")]
     SeekNotAllowed,
+    #[error("The input isn't seekable, which requires FrameSelection::FirstKeyframe")]
+    NonSeekableInput,
     #[error("Received an invalid seek percentage: {0}")]
     InvalidSeekPercentage(f32),
     #[error("Received an invalid quality, expected range [0.0, 100.0], received: {0}")]
     InvalidQuality(f32),
+    #[error("Received an invalid luminance threshold, expected range [0.0, 255.0], received: {0}")]
+    InvalidLuminanceThreshold(f32),
+    #[error("Received an invalid percentile, expected range [0.0, 100.0], received: {0}")]
+    InvalidPercentile(f32),
+    #[error("Received an invalid crop, expected fractions in range [0.0, 1.0) leaving a region, received: top {top}, bottom {bottom}, left {left}, right {right}")]
+    InvalidCrop {
+        top: f32,
+        bottom: f32,
+        left: f32,
+        right: f32,
+    },
+    #[error(
+        "Received an invalid sprite sheet grid of {columns}x{rows}, expected at least one cell"
+    )]
+    InvalidGrid { columns: u32, rows: u32 },
+    #[error("Received an invalid denoise strength, expected a non-negative value, received: {0}")]
+    InvalidDenoiseStrength(f32),
+    #[error("Received an invalid brightness, expected range [-1.0, 1.0], received: {0}")]
+    InvalidBrightness(f32),
+    #[error("Received an invalid gamma, expected a positive value, received: {0}")]
+    InvalidGamma(f32),
+    #[error("Received an invalid play button size, expected range (0.0, 1.0], received: {0}")]
+    InvalidPlayButtonSize(f32),
+    #[error("Received an invalid opacity, expected range [0.0, 1.0], received: {0}")]
+    InvalidOpacity(f32),
+    #[cfg(feature = "webp")]
+    #[error("Received an invalid near-lossless level, expected range [0, 100], received: {0}")]
+    InvalidNearLossless(u8),
+    #[cfg(feature = "face-detect")]
+    #[error("FrameSelection::WithFace requires a face detection model")]
+    MissingFaceDetectionModel,
+    #[error("Received an invalid byte range, expected start < end, received: {start}..{end}")]
+    InvalidByteRange { start: u64, end: u64 },
+    #[error("No decodable video frame in bytes {start}..{end} of the input")]
+    NoFrameInByteRange { start: u64, end: u64 },
+    #[error("No attachment found at stream index {0}")]
+    AttachmentNotFound(usize),
     #[error("Background task failed: {0}")]
     BackgroundTaskFailed(#[from] JoinError),
+    #[error("Source dimensions {width}x{height} exceed the maximum of {max_source_dimension}")]
+    SourceTooLarge {
+        width: u32,
+        height: u32,
+        max_source_dimension: u32,
+    },
+    #[error("The smallest thumbnail of {size} bytes exceeds the maximum of {max_bytes}")]
+    OutputTooLarge { size: usize, max_bytes: usize },
+    #[error("None of the requested output formats could be encoded")]
+    NoSupportedFormat,
+    #[error(
+        "Unrecognized input format, the input may not be a video, guessed format from its name: {}",
+        .0.as_deref().unwrap_or("none")
+    )]
+    UnrecognizedFormat(Option<String>),
     #[error("Unsupported file extension: {0:?}")]
     UnsupportedExtension(std::ffi::OsString),
+    #[cfg(feature = "webp")]
+    #[error("WebP encoding failed: {0:?}")]
+    WebpEncodingError(libwebp_sys::WebPEncodingError),
     #[cfg(feature = "png")]
     #[error("PNG encoding failed: {0}")]
     PngEncodingError(#[from] png::EncodingError),
+    #[cfg(feature = "jpeg")]
+    #[error("JPEG encoding failed: {0}")]
+    JpegEncodingError(#[from] jpeg_encoder::EncodingError),
+    #[error("Custom encoder failed: {0}")]
+    CustomEncodingError(Box<dyn std::error::Error + Send + Sync>),
+    #[cfg(feature = "jpeg")]
+    #[error("Thumbnail dimensions {width}x{height} exceed the JPEG maximum of 65535")]
+    JpegDimensionsTooLarge { width: u32, height: u32 },
+}
+
+/// Broad category of a `ThumbnailerError`, stable across releases
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Reading the input or writing the output failed
+    Io,
+    /// The input is corrupted or couldn't be decoded
+    Decode,
+    /// The thumbnail couldn't be encoded
+    Encode,
+    /// The input, or something it requires, isn't supported by the library or FFmpeg build
+    Unsupported,
+    /// The input path can't be handed to FFmpeg, or doesn't hold what was asked for
+    InvalidInput,
+    /// An option set on the `ThumbnailerBuilder` is out of range
+    InvalidConfig,
+    /// The input exceeds a limit set on the `ThumbnailerBuilder`
+    ResourceLimit,
+    /// Unexpected failure inside the library or FFmpeg
+    Internal,
+}
+
+impl ThumbnailerError {
+    /// Category of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ThumbnailerError::Io(_) | ThumbnailerError::OpenTimeout(_) => ErrorKind::Io,
+            ThumbnailerError::PathConversion(_)
+            | ThumbnailerError::EmptyInput
+            | ThumbnailerError::InvalidCueSheet { .. }
+            | ThumbnailerError::AttachmentNotFound(_)
+            | ThumbnailerError::InvalidByteRange { .. } => ErrorKind::InvalidInput,
+            ThumbnailerError::Ffmpeg(e) | ThumbnailerError::FfmpegWithReason(e, _) => e.kind(),
+            ThumbnailerError::FrameDecodeError
+            | ThumbnailerError::SeekError
+            | ThumbnailerError::TruncatedFile
+            | ThumbnailerError::NoFrameInByteRange { .. } => ErrorKind::Decode,
+            ThumbnailerError::SeekNotAllowed => ErrorKind::Unsupported,
+            ThumbnailerError::NonSeekableInput
+            | ThumbnailerError::InvalidSeekPercentage(_)
+            | ThumbnailerError::InvalidQuality(_)
+            | ThumbnailerError::InvalidLuminanceThreshold(_)
+            | ThumbnailerError::InvalidPercentile(_)
+            | ThumbnailerError::InvalidCrop { .. }
+            | ThumbnailerError::InvalidGrid { .. }
+            | ThumbnailerError::InvalidDenoiseStrength(_)
+            | ThumbnailerError::InvalidBrightness(_)
+            | ThumbnailerError::InvalidGamma(_)
+            | ThumbnailerError::InvalidPlayButtonSize(_)
+            | ThumbnailerError::InvalidOpacity(_) => ErrorKind::InvalidConfig,
+            #[cfg(feature = "webp")]
+            ThumbnailerError::InvalidNearLossless(_) => ErrorKind::InvalidConfig,
+            #[cfg(feature = "face-detect")]
+            ThumbnailerError::MissingFaceDetectionModel => ErrorKind::InvalidConfig,
+            ThumbnailerError::BackgroundTaskFailed(_) => ErrorKind::Internal,
+            ThumbnailerError::SourceTooLarge { .. } | ThumbnailerError::OutputTooLarge { .. } => {
+                ErrorKind::ResourceLimit
+            }
+            ThumbnailerError::NoSupportedFormat
+            | ThumbnailerError::UnrecognizedFormat(_)
+            | ThumbnailerError::UnsupportedExtension(_) => ErrorKind::Unsupported,
+            ThumbnailerError::CustomEncodingError(_) => ErrorKind::Encode,
+            #[cfg(feature = "webp")]
+            ThumbnailerError::WebpEncodingError(_) => ErrorKind::Encode,
+            #[cfg(feature = "png")]
+            ThumbnailerError::PngEncodingError(_) => ErrorKind::Encode,
+            #[cfg(feature = "jpeg")]
+            ThumbnailerError::JpegEncodingError(_)
+            | ThumbnailerError::JpegDimensionsTooLarge { .. } => ErrorKind::Encode,
+        }
+    }
 }
 
 /// Enum to represent possible errors from FFMPEG library
@@ -110,10 +261,52 @@ pub enum FfmpegError {
     VideoCodecAllocation,
     #[error("Filter Graph allocation error")]
     FilterGraphAllocation,
+    #[error("I/O Context allocation error")]
+    IoContextAllocation,
     #[error("Codec Open Error")]
     CodecOpen,
 }
 
+impl FfmpegError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            FfmpegError::BitstreamFilterNotFound
+            | FfmpegError::DecoderNotFound
+            | FfmpegError::DemuxerNotFound
+            | FfmpegError::EncoderNotFound
+            | FfmpegError::FilterNotFound
+            | FfmpegError::MuxerNotFound
+            | FfmpegError::OptionNotFound
+            | FfmpegError::NotImplemented
+            | FfmpegError::ProtocolNotFound
+            | FfmpegError::StreamNotFound
+            | FfmpegError::Experimental => ErrorKind::Unsupported,
+            FfmpegError::Eof
+            | FfmpegError::InvalidData
+            | FfmpegError::InputChanged
+            | FfmpegError::OutputChanged
+            | FfmpegError::CodecOpen => ErrorKind::Decode,
+            FfmpegError::Exit
+            | FfmpegError::HttpBadRequest
+            | FfmpegError::HttpUnauthorized
+            | FfmpegError::HttpForbidden
+            | FfmpegError::HttpNotFound
+            | FfmpegError::HttpOther4xx
+            | FfmpegError::HttpServerError
+            | FfmpegError::OtherOSError(_) => ErrorKind::Io,
+            FfmpegError::InternalBug
+            | FfmpegError::InternalBug2
+            | FfmpegError::BufferTooSmall
+            | FfmpegError::External
+            | FfmpegError::Unknown
+            | FfmpegError::FrameAllocation
+            | FfmpegError::VideoCodecAllocation
+            | FfmpegError::FilterGraphAllocation
+            | FfmpegError::IoContextAllocation => ErrorKind::Internal,
+        }
+    }
+}
+
 impl From<c_int> for FfmpegError {
     fn from(code: c_int) -> Self {
         match code {
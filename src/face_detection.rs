@@ -0,0 +1,70 @@
+use crate::{ThumbnailerError, VideoFrame};
+
+use rustface::{Detector, ImageData};
+use std::{fs::File, io::BufReader, path::Path};
+
+/// Smallest face, in pixels of the scaled frame, looked for by the detector
+const MIN_FACE_SIZE: u32 = 20;
+
+/// Loads the SeetaFace frontal face model at `model_path` into a new detector
+pub(crate) fn load_detector(model_path: &Path) -> Result<Box<dyn Detector>, ThumbnailerError> {
+    let model = rustface::read_model(BufReader::new(File::open(model_path)?))?;
+    let mut detector = rustface::create_detector_with_model(model);
+    detector.set_min_face_size(MIN_FACE_SIZE);
+    Ok(detector)
+}
+
+/// Whether `detector` finds at least one face in the RGB24 pixels of `video_frame`
+pub(crate) fn contains_face(detector: &mut dyn Detector, video_frame: &VideoFrame) -> bool {
+    let gray = to_grayscale(video_frame);
+    if gray.is_empty() {
+        return false;
+    }
+    !detector
+        .detect(&ImageData::new(
+            &gray,
+            video_frame.width,
+            video_frame.height,
+        ))
+        .is_empty()
+}
+
+/// Luminance of the RGB24 pixels, using the BT.601 weights, packed without row padding
+fn to_grayscale(video_frame: &VideoFrame) -> Vec<u8> {
+    let row_length = video_frame.width as usize * 3;
+    let mut gray = Vec::with_capacity(video_frame.width as usize * video_frame.height as usize);
+    for row in video_frame
+        .data
+        .chunks(video_frame.line_size as usize)
+        .take(video_frame.height as usize)
+    {
+        for pixel in row[..row_length.min(row.len())].chunks_exact(3) {
+            gray.push(
+                ((299 * pixel[0] as u32 + 587 * pixel[1] as u32 + 114 * pixel[2] as u32 + 500)
+                    / 1000) as u8,
+            );
+        }
+    }
+    gray
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_grayscale_skips_row_padding() {
+        // 2x2 frame stored with a stride of 8, the last 2 bytes of each row being padding
+        let video_frame = VideoFrame {
+            width: 2,
+            height: 2,
+            line_size: 8,
+            data: vec![
+                255, 255, 255, 0, 0, 0, 7, 7, //
+                255, 0, 0, 0, 255, 0, 7, 7,
+            ],
+            ..Default::default()
+        };
+        assert_eq!(to_grayscale(&video_frame), vec![255, 0, 76, 150]);
+    }
+}
@@ -1,5 +1,9 @@
+//! Film strip decoration drawn on the sides of thumbnails, also usable on any RGB24 image
+
 use crate::video_frame::VideoFrame;
 
+use std::borrow::Cow;
+
 static FILM_STRIP_4: [u8; 4 * 4 * 3] = [
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 107, 107, 107, 135, 135, 135, 55, 55, 55, 0, 0, 0,
     159, 159, 159, 195, 195, 195, 82, 82, 82, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -622,72 +626,234 @@ static FILM_STRIP_64: [u8; 64 * 64 * 3] = [
 struct FilmStrip {
     width: u32,
     height: u32,
-    strip: Option<&'static [u8]>,
+    strip: Option<Cow<'static, [u8]>>,
+}
+
+/// Configuration of the film strip decoration drawn by `apply`, also set on thumbnails with
+/// `ThumbnailerBuilder::film_strip_config`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilmStripConfig {
+    /// Maximum width, in pixels, of the strip drawn on each side, the largest of the 4, 8, 16, 32
+    /// and 64 pixels wide strips fitting in it being used. By default the strip width follows the
+    /// image width, as for thumbnails.
+    pub strip_width: Option<u32>,
+    /// Width of the strip drawn on each side, in thousandths of the smaller image dimension, the
+    /// holes and border being scaled to it so that the decoration keeps its proportions whatever
+    /// the thumbnail size. Takes precedence over `strip_width`
+    pub width_per_mille: Option<u32>,
+    /// Whether the strip is drawn along the left edge
+    pub left: bool,
+    /// Whether the strip is drawn along the right edge
+    pub right: bool,
+    /// Whether the strip is drawn along the top edge, rotated to run horizontally
+    pub top: bool,
+    /// Whether the strip is drawn along the bottom edge, rotated to run horizontally
+    pub bottom: bool,
+    /// Whether the black border around the sprocket holes is drawn, otherwise only the holes are
+    /// drawn over the image
+    pub border: bool,
+}
+
+impl Default for FilmStripConfig {
+    /// Strips on the left and right edges, their width following the image width
+    fn default() -> Self {
+        Self {
+            strip_width: None,
+            width_per_mille: None,
+            left: true,
+            right: true,
+            top: false,
+            bottom: false,
+            border: true,
+        }
+    }
+}
+
+impl FilmStripConfig {
+    fn film_strip(&self, video_width: u32, video_height: u32) -> FilmStrip {
+        if let Some(width_per_mille) = self.width_per_mille {
+            let size = video_width.min(video_height) as u64 * width_per_mille as u64 / 1000;
+            // Both strips must fit side by side
+            return scaled_film_strip((size as u32).min(video_width / 2));
+        }
+        match self.strip_width {
+            Some(strip_width) if strip_width < determine_film_strip(video_width).width => {
+                film_strip_of_size(strip_width)
+            }
+            _ => determine_film_strip(video_width),
+        }
+    }
+}
+
+/// Draws the film strip decoration on the edges enabled in `config` of an RGB24 image whose rows
+/// are tightly packed, as `Thumbnailer` does with `with_film_strip`. Images too narrow to hold a
+/// strip on each side are left untouched.
+///
+/// # Panics
+///
+/// Panics if `data` is shorter than `width * height * 3` bytes.
+pub fn apply(data: &mut [u8], width: u32, height: u32, config: FilmStripConfig) {
+    assert!(
+        data.len() >= width as usize * height as usize * 3,
+        "RGB buffer is too small for a {width}x{height} image"
+    );
+
+    draw_film_strip(data, width, height, width * 3, config);
+}
+
+pub(crate) fn film_strip_filter(video_frame: &mut VideoFrame, config: FilmStripConfig) {
+    draw_film_strip(
+        &mut video_frame.data,
+        video_frame.width,
+        video_frame.height,
+        video_frame.line_size,
+        config,
+    );
 }
 
-pub(crate) fn film_strip_filter(video_frame: &mut VideoFrame) {
+fn draw_film_strip(
+    data: &mut [u8],
+    video_width: u32,
+    video_height: u32,
+    line_size: u32,
+    config: FilmStripConfig,
+) {
     let FilmStrip {
         width,
         height,
         strip,
-    } = determine_film_strip(video_frame.width);
+    } = config.film_strip(video_width, video_height);
 
-    if let Some(strip) = strip {
+    if let Some(strip) = strip.as_deref() {
+        // The border is the black part of the strip, around the holes
+        let draw = |data: &mut [u8], index: usize, pixel: &[u8]| {
+            if config.border || pixel.iter().any(|&value| value != 0) {
+                data[index..index + 3].copy_from_slice(pixel);
+            }
+        };
         let mut frame_index = 0;
         let mut film_hole_index = 0;
-        let offset = ((video_frame.width * 3) - 3) as usize;
+        let offset = ((video_width * 3) - 3) as usize;
 
-        for i in 0..(video_frame.height as usize) {
+        for i in 0..(video_height as usize) {
             for j in (0..(width as usize * 3)).step_by(3) {
                 let current_stripe_index = film_hole_index + j;
+                let pixel = &strip[current_stripe_index..current_stripe_index + 3];
 
-                video_frame.data[frame_index + j] = strip[current_stripe_index];
-                video_frame.data[frame_index + j + 1] = strip[current_stripe_index + 1];
-                video_frame.data[frame_index + j + 2] = strip[current_stripe_index + 2];
-
-                video_frame.data[frame_index + offset - j] = strip[current_stripe_index];
-                video_frame.data[frame_index + offset - j + 1] = strip[current_stripe_index + 1];
-                video_frame.data[frame_index + offset - j + 2] = strip[current_stripe_index + 2];
+                if config.left {
+                    draw(data, frame_index + j, pixel);
+                }
+                if config.right {
+                    draw(data, frame_index + offset - j, pixel);
+                }
             }
 
-            frame_index += video_frame.line_size as usize;
+            frame_index += line_size as usize;
             film_hole_index = (i % height as usize) * width as usize * 3;
         }
+
+        // The strip is transposed to run along the horizontal edges, the holes following the
+        // columns as they follow the rows on the vertical edges
+        let rows = if config.top || config.bottom {
+            (width as usize).min(video_height as usize)
+        } else {
+            0
+        };
+        for y in 0..rows {
+            for x in 0..(video_width as usize) {
+                let current_stripe_index = ((x % height as usize) * width as usize + y) * 3;
+                let pixel = &strip[current_stripe_index..current_stripe_index + 3];
+
+                if config.top {
+                    draw(data, y * line_size as usize + x * 3, pixel);
+                }
+                if config.bottom {
+                    draw(
+                        data,
+                        (video_height as usize - 1 - y) * line_size as usize + x * 3,
+                        pixel,
+                    );
+                }
+            }
+        }
     }
 }
 
 fn determine_film_strip(video_width: u32) -> FilmStrip {
     match video_width {
         // We consider that the smallest film strip is 4, doubling it for each side, we have 8 pixels
-        0..=8 => FilmStrip {
+        0..=8 => film_strip_of_size(0),
+        9..=96 => film_strip_of_size(4),
+        97..=192 => film_strip_of_size(8),
+        193..=384 => film_strip_of_size(16),
+        385..=768 => film_strip_of_size(32),
+        _ => film_strip_of_size(64),
+    }
+}
+
+/// Film strip exactly `size` pixels wide, resampled from the smallest one at least as wide, or
+/// the widest one
+fn scaled_film_strip(size: u32) -> FilmStrip {
+    let source = film_strip_of_size(size.max(4).next_power_of_two().min(64));
+    let (source_size, source_strip) = match (size, source.strip) {
+        (0, _) | (_, None) => {
+            return FilmStrip {
+                width: 0,
+                height: 0,
+                strip: None,
+            }
+        }
+        (_, Some(strip)) => (source.width as usize, strip),
+    };
+
+    let size = size as usize;
+    let mut strip = Vec::with_capacity(size * size * 3);
+    for y in 0..size {
+        let source_y = y * source_size / size;
+        for x in 0..size {
+            let index = (source_y * source_size + x * source_size / size) * 3;
+            strip.extend_from_slice(&source_strip[index..index + 3]);
+        }
+    }
+    FilmStrip {
+        width: size as u32,
+        height: size as u32,
+        strip: Some(Cow::Owned(strip)),
+    }
+}
+
+/// The largest film strip at most `size` pixels wide
+fn film_strip_of_size(size: u32) -> FilmStrip {
+    match size {
+        0..=3 => FilmStrip {
             width: 0,
             height: 0,
             strip: None,
         },
-        9..=96 => FilmStrip {
+        4..=7 => FilmStrip {
             width: 4,
             height: 4,
-            strip: Some(&FILM_STRIP_4),
+            strip: Some(Cow::Borrowed(&FILM_STRIP_4)),
         },
-        97..=192 => FilmStrip {
+        8..=15 => FilmStrip {
             width: 8,
             height: 8,
-            strip: Some(&FILM_STRIP_8),
+            strip: Some(Cow::Borrowed(&FILM_STRIP_8)),
         },
-        193..=384 => FilmStrip {
+        16..=31 => FilmStrip {
             width: 16,
             height: 16,
-            strip: Some(&FILM_STRIP_16),
+            strip: Some(Cow::Borrowed(&FILM_STRIP_16)),
         },
-        385..=768 => FilmStrip {
+        32..=63 => FilmStrip {
             width: 32,
             height: 32,
-            strip: Some(&FILM_STRIP_32),
+            strip: Some(Cow::Borrowed(&FILM_STRIP_32)),
         },
         _ => FilmStrip {
             width: 64,
             height: 64,
-            strip: Some(&FILM_STRIP_64),
+            strip: Some(Cow::Borrowed(&FILM_STRIP_64)),
         },
     }
 }
@@ -0,0 +1,448 @@
+use crate::VideoFrame;
+
+use std::time::Duration;
+
+/// Brightness and gamma adjustments applied to the RGB24 pixels of scaled frames, set with
+/// `ThumbnailerBuilder::brightness` and `ThumbnailerBuilder::gamma`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ColorAdjustment {
+    /// Offset in range [-1.0, 1.0] added to every channel, 1.0 being the full channel range
+    pub(crate) brightness: f32,
+    /// Exponent applied as `value^(1 / gamma)` to every channel, values above 1.0 lifting the
+    /// shadows
+    pub(crate) gamma: f32,
+}
+
+impl Default for ColorAdjustment {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl ColorAdjustment {
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Output value of each of the 256 channel values, the gamma being applied before the
+    /// brightness
+    fn lookup_table(&self) -> [u8; 256] {
+        let mut table = [0; 256];
+        for (value, output) in table.iter_mut().enumerate() {
+            let corrected = (value as f32 / 255.0).powf(1.0 / self.gamma) + self.brightness;
+            *output = (corrected * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+        table
+    }
+
+    pub(crate) fn apply(&self, video_frame: &mut VideoFrame) {
+        if self.is_identity() {
+            return;
+        }
+
+        let table = self.lookup_table();
+        let row_length = video_frame.width as usize * 3;
+        for row in video_frame
+            .data
+            .chunks_mut(video_frame.line_size as usize)
+            .take(video_frame.height as usize)
+        {
+            let row_length = row_length.min(row.len());
+            for value in &mut row[..row_length] {
+                *value = table[*value as usize];
+            }
+        }
+    }
+}
+
+/// Centers the RGB24 pixels of `video_frame` on a square canvas filled with `color`, as wide as
+/// the longest side of the frame, so that the whole frame stays visible
+pub(crate) fn pad_to_square(video_frame: &mut VideoFrame, color: [u8; 3]) {
+    let (width, height) = (video_frame.width as usize, video_frame.height as usize);
+    let side = width.max(height);
+    if width == height {
+        return;
+    }
+
+    let line_size = side * 3;
+    let mut data = color.repeat(side * side);
+    let (left, top) = ((side - width) / 2, (side - height) / 2);
+    for (y, row) in video_frame
+        .data
+        .chunks(video_frame.line_size as usize)
+        .take(height)
+        .enumerate()
+    {
+        let start = (top + y) * line_size + left * 3;
+        let row_length = (width * 3).min(row.len());
+        data[start..start + row_length].copy_from_slice(&row[..row_length]);
+    }
+
+    video_frame.width = side as u32;
+    video_frame.height = side as u32;
+    video_frame.line_size = line_size as u32;
+    video_frame.data = data;
+}
+
+/// Play button drawn centered on scaled frames, as a white triangle on a darkened disc, set with
+/// `ThumbnailerBuilder::play_button_overlay`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PlayButton {
+    /// Diameter of the disc, as a fraction in range (0.0, 1.0] of the shorter side of the frame
+    pub(crate) size: f32,
+    /// Opacity of the triangle in range [0.0, 1.0], the disc being half as opaque
+    pub(crate) opacity: f32,
+}
+
+impl Default for PlayButton {
+    fn default() -> Self {
+        Self {
+            size: 0.25,
+            opacity: 0.8,
+        }
+    }
+}
+
+impl PlayButton {
+    /// Blends the play button into the RGB24 pixels of `video_frame`, with edges antialiased
+    /// over a pixel
+    pub(crate) fn apply(&self, video_frame: &mut VideoFrame) {
+        let (width, height) = (video_frame.width as usize, video_frame.height as usize);
+        let radius = width.min(height) as f32 * self.size / 2.0;
+        if radius < 1.0 || self.opacity == 0.0 {
+            return;
+        }
+
+        let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+        let first_x = (center_x - radius).floor().max(0.0) as usize;
+        let last_x = ((center_x + radius).ceil() as usize).min(width);
+        let first_y = (center_y - radius).floor().max(0.0) as usize;
+        let last_y = ((center_y + radius).ceil() as usize).min(height);
+        for (y, row) in video_frame
+            .data
+            .chunks_mut(video_frame.line_size as usize)
+            .enumerate()
+            .take(last_y)
+            .skip(first_y)
+        {
+            for x in first_x..last_x {
+                let dx = x as f32 + 0.5 - center_x;
+                let dy = y as f32 + 0.5 - center_y;
+                let disc = coverage(radius - (dx * dx + dy * dy).sqrt());
+                if disc == 0.0 {
+                    continue;
+                }
+                let triangle = coverage(triangle_distance(dx / radius, dy / radius) * radius);
+
+                for value in &mut row[x * 3..x * 3 + 3] {
+                    let darkened = *value as f32 * (1.0 - self.opacity * 0.5 * disc);
+                    let alpha = self.opacity * triangle;
+                    *value = (darkened * (1.0 - alpha) + 255.0 * alpha).round() as u8;
+                }
+            }
+        }
+    }
+}
+
+/// Fraction of a pixel covered by a shape whose edge is `distance` pixels away from the pixel
+/// center, positive inside
+fn coverage(distance: f32) -> f32 {
+    (distance + 0.5).clamp(0.0, 1.0)
+}
+
+/// Signed distance, positive inside, of a point to the play triangle, in units of the disc
+/// radius. The triangle points right, its vertices being (-0.25, ±0.4) and (0.5, 0) so that it
+/// looks centered in the disc
+fn triangle_distance(x: f32, y: f32) -> f32 {
+    let back = x + 0.25;
+    // Normalized by the length of the (0.75, 0.4) edge direction
+    let slopes = ((0.5 - x) * 0.4 - 0.75 * y.abs()) / 0.85;
+    back.min(slopes)
+}
+
+/// Corner of the thumbnail a label is drawn in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// 3x5 pixel glyphs of the digits then `:` and `.`, each row being 3 bits, leftmost first
+const GLYPHS: [[u8; 5]; 12] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+    [0b000, 0b010, 0b000, 0b010, 0b000],
+    [0b000, 0b000, 0b000, 0b000, 0b010],
+];
+
+/// `timestamp` as `MM:SS.mmm`, prefixed with the hours when there are some
+fn format_timestamp(timestamp: Duration) -> String {
+    let seconds = timestamp.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    let millis = timestamp.subsec_millis();
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}.{millis:03}")
+    } else {
+        format!("{minutes:02}:{seconds:02}.{millis:03}")
+    }
+}
+
+/// Draws `timestamp` in white on a black box in `corner` of the RGB24 pixels of `video_frame`,
+/// glyphs being scaled up with the frame height. Pixels past the frame edges are left out
+pub(crate) fn burn_timestamp(video_frame: &mut VideoFrame, timestamp: Duration, corner: Corner) {
+    let text = format_timestamp(timestamp);
+    let (width, height) = (video_frame.width as usize, video_frame.height as usize);
+    let scale = (height / 60).max(1);
+    // Glyphs are 3 pixels wide, followed by a pixel of spacing, and the box has a 1 pixel border
+    let box_width = (text.len() * 4 + 1) * scale;
+    let box_height = 7 * scale;
+    let margin = 2 * scale;
+    let left = match corner {
+        Corner::TopLeft | Corner::BottomLeft => margin,
+        Corner::TopRight | Corner::BottomRight => width.saturating_sub(box_width + margin),
+    };
+    let top = match corner {
+        Corner::TopLeft | Corner::TopRight => margin,
+        Corner::BottomLeft | Corner::BottomRight => height.saturating_sub(box_height + margin),
+    };
+
+    let line_size = video_frame.line_size as usize;
+    let mut fill = |x: usize, y: usize, value: u8| {
+        if x < width && y < height {
+            let start = y * line_size + x * 3;
+            video_frame.data[start..start + 3].fill(value);
+        }
+    };
+    for y in top..top + box_height {
+        for x in left..left + box_width {
+            fill(x, y, 0);
+        }
+    }
+    for (index, character) in text.bytes().enumerate() {
+        let glyph = match character {
+            b'0'..=b'9' => GLYPHS[(character - b'0') as usize],
+            b':' => GLYPHS[10],
+            _ => GLYPHS[11],
+        };
+        let glyph_left = left + (index * 4 + 1) * scale;
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in (0..3).filter(|column| bits & (0b100 >> column) != 0) {
+                for y in 0..scale {
+                    for x in 0..scale {
+                        fill(
+                            glyph_left + column * scale + x,
+                            top + (row + 1) * scale + y,
+                            255,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Running per-channel sums of the RGB24 pixels of frames of identical dimensions, blended into
+/// their mean by `FrameSelection::AverageOfN`
+#[derive(Debug, Default)]
+pub(crate) struct FrameAccumulator {
+    width: u32,
+    height: u32,
+    // Wide enough for far more frames than could ever be decoded
+    sums: Vec<u64>,
+    count: u64,
+}
+
+impl FrameAccumulator {
+    /// Adds the pixels of `video_frame`, ignored if its dimensions differ from the first one's
+    pub(crate) fn add(&mut self, video_frame: &VideoFrame) {
+        if self.count == 0 {
+            self.width = video_frame.width;
+            self.height = video_frame.height;
+            self.sums = vec![0; self.width as usize * self.height as usize * 3];
+        } else if (video_frame.width, video_frame.height) != (self.width, self.height) {
+            return;
+        }
+
+        let row_length = self.width as usize * 3;
+        for (row, sums) in video_frame
+            .data
+            .chunks(video_frame.line_size as usize)
+            .take(self.height as usize)
+            .zip(self.sums.chunks_mut(row_length))
+        {
+            for (sum, &value) in sums.iter_mut().zip(row) {
+                *sum += value as u64;
+            }
+        }
+        self.count += 1;
+    }
+
+    /// Overwrites the pixels of `video_frame`, of the same dimensions as the added frames, with
+    /// their rounded mean
+    pub(crate) fn average_into(&self, video_frame: &mut VideoFrame) {
+        if self.count == 0 {
+            return;
+        }
+
+        let row_length = self.width as usize * 3;
+        for (row, sums) in video_frame
+            .data
+            .chunks_mut(video_frame.line_size as usize)
+            .take(self.height as usize)
+            .zip(self.sums.chunks(row_length))
+        {
+            for (value, &sum) in row.iter_mut().zip(sums) {
+                *value = ((sum + self.count / 2) / self.count) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_identity() {
+        let table = ColorAdjustment::default().lookup_table();
+        assert!(table
+            .iter()
+            .enumerate()
+            .all(|(value, output)| value as u8 == *output));
+    }
+
+    #[test]
+    fn brightness_saturates() {
+        let table = ColorAdjustment {
+            brightness: 0.2,
+            gamma: 1.0,
+        }
+        .lookup_table();
+        assert_eq!((table[0], table[100], table[220]), (51, 151, 255));
+    }
+
+    #[test]
+    fn gamma_lifts_shadows() {
+        let table = ColorAdjustment {
+            brightness: 0.0,
+            gamma: 2.0,
+        }
+        .lookup_table();
+        assert_eq!((table[0], table[64], table[255]), (0, 128, 255));
+    }
+
+    #[test]
+    fn apply_skips_row_padding() {
+        let mut video_frame = VideoFrame {
+            width: 1,
+            height: 2,
+            line_size: 4,
+            data: vec![0, 0, 0, 7, 0, 0, 0, 7],
+            ..Default::default()
+        };
+        ColorAdjustment {
+            brightness: 1.0,
+            gamma: 1.0,
+        }
+        .apply(&mut video_frame);
+        assert_eq!(video_frame.data, vec![255, 255, 255, 7, 255, 255, 255, 7]);
+    }
+
+    #[test]
+    fn play_button_is_centered() {
+        let mut video_frame = VideoFrame {
+            width: 32,
+            height: 32,
+            line_size: 96,
+            data: vec![100; 32 * 32 * 3],
+            ..Default::default()
+        };
+        PlayButton {
+            size: 1.0,
+            opacity: 1.0,
+        }
+        .apply(&mut video_frame);
+        let pixel = |x: usize, y: usize| video_frame.data[y * 96 + x * 3];
+        // Triangle, corner outside the disc, and disc left of the triangle
+        assert_eq!((pixel(16, 16), pixel(0, 0), pixel(2, 16)), (255, 100, 50));
+    }
+
+    #[test]
+    fn timestamp_shows_hours_only_when_needed() {
+        assert_eq!(format_timestamp(Duration::from_millis(83_500)), "01:23.500");
+        assert_eq!(
+            format_timestamp(Duration::from_millis(3_723_004)),
+            "1:02:03.004"
+        );
+    }
+
+    #[test]
+    fn timestamp_is_drawn_in_its_corner() {
+        let mut video_frame = VideoFrame {
+            width: 64,
+            height: 16,
+            line_size: 64 * 3,
+            data: vec![128; 64 * 16 * 3],
+            ..Default::default()
+        };
+        burn_timestamp(&mut video_frame, Duration::ZERO, Corner::TopLeft);
+        let pixel = |x: usize, y: usize| video_frame.data[y * 64 * 3 + x * 3];
+        // Box border, top left pixel of the first 0, and untouched bottom right
+        assert_eq!((pixel(2, 2), pixel(3, 3), pixel(63, 15)), (0, 255, 128));
+    }
+
+    #[test]
+    fn accumulator_averages_without_overflow() {
+        let frame = |value| VideoFrame {
+            width: 1,
+            height: 1,
+            line_size: 4,
+            data: vec![value, value, value, 7],
+            ..Default::default()
+        };
+        let mut accumulator = FrameAccumulator::default();
+        for value in [255, 255, 255, 0] {
+            accumulator.add(&frame(value));
+        }
+
+        let mut video_frame = frame(0);
+        accumulator.average_into(&mut video_frame);
+        assert_eq!(video_frame.data, vec![191, 191, 191, 7]);
+    }
+
+    #[test]
+    fn pad_to_square_centers_frame() {
+        let mut video_frame = VideoFrame {
+            width: 1,
+            height: 3,
+            line_size: 4,
+            data: vec![1, 1, 1, 0, 2, 2, 2, 0, 3, 3, 3, 0],
+            ..Default::default()
+        };
+        pad_to_square(&mut video_frame, [9, 9, 9]);
+        assert_eq!((video_frame.width, video_frame.height), (3, 3));
+        assert_eq!(video_frame.line_size, 9);
+        assert_eq!(
+            video_frame.data,
+            vec![
+                9, 9, 9, 1, 1, 1, 9, 9, 9, //
+                9, 9, 9, 2, 2, 2, 9, 9, 9, //
+                9, 9, 9, 3, 3, 3, 9, 9, 9,
+            ]
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use crate::{MovieDecoder, ThumbnailerError, VideoFrame};
+
+use std::{fmt, sync::Arc};
+
+/// Chooses the frame that becomes the thumbnail, replacing `FrameSelection` when set with
+/// `ThumbnailerBuilder::frame_selector`, e.g. for heuristics the crate doesn't provide
+pub trait FrameSelector: Send + Sync {
+    /// Moves `decoder`, opened on the frame at the seek position, to the chosen frame and returns
+    /// it as given by `MovieDecoder::thumbnail_frame`. Color adjustments, padding and the film
+    /// strip are applied to the returned frame afterwards
+    fn select(&self, decoder: &mut MovieDecoder) -> Result<VideoFrame, ThumbnailerError>;
+}
+
+/// Shared handle on a custom `FrameSelector`, so that the `ThumbnailerBuilder` holding it stays
+/// cloneable
+#[derive(Clone)]
+pub(crate) struct CustomFrameSelector(pub(crate) Arc<dyn FrameSelector>);
+
+impl fmt::Debug for CustomFrameSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomFrameSelector")
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{
+    MovieDecoder, OutputContainer, OutputFormat, ThumbnailerBuilder, ThumbnailerError, VideoFrame,
+};
+
+use std::{
+    collections::VecDeque,
+    mem,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+/// `FrameSource` keeps a video file open, obtained from `Thumbnailer::open`, to extract frames at
+/// arbitrary timestamps without reopening and probing the file each time.
+pub struct FrameSource {
+    builder: ThumbnailerBuilder,
+    output_format: OutputFormat,
+    state: Arc<Mutex<FrameSourceState>>,
+}
+
+struct FrameSourceState {
+    decoder: MovieDecoder,
+    // Scratch frame whose buffer is reused by every extraction, frames of a given source all
+    // having the same dimensions
+    video_frame: VideoFrame,
+    cache: FrameCache,
+}
+
+/// Least recently used cache of the scaled frames extracted by a `FrameSource`, so that
+/// scrubbing back and forth around the same timestamps doesn't seek and decode again
+struct FrameCache {
+    capacity: usize,
+    tolerance: Duration,
+    // Least recently used first
+    entries: VecDeque<(Duration, VideoFrame)>,
+}
+
+impl FrameCache {
+    fn new(capacity: usize, tolerance: Duration) -> Self {
+        Self {
+            capacity,
+            tolerance,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// The cached frame closest to `timestamp`, if within the tolerance, marked as most recently
+    /// used
+    fn get(&mut self, timestamp: Duration) -> Option<&VideoFrame> {
+        let distance = |cached: Duration| {
+            if cached > timestamp {
+                cached - timestamp
+            } else {
+                timestamp - cached
+            }
+        };
+        let index = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, (cached, _))| (index, distance(*cached)))
+            .filter(|&(_, distance)| distance <= self.tolerance)
+            .min_by_key(|&(_, distance)| distance)
+            .map(|(index, _)| index)?;
+
+        let entry = self.entries.remove(index)?;
+        self.entries.push_back(entry);
+        self.entries.back().map(|(_, video_frame)| video_frame)
+    }
+
+    /// Moves `video_frame` into the cache, handing back the evicted frame, if any, so that its
+    /// buffer is reused by the next extraction
+    fn insert(&mut self, timestamp: Duration, video_frame: &mut VideoFrame) {
+        if self.capacity == 0 {
+            return;
+        }
+        let spare = if self.entries.len() >= self.capacity {
+            self.entries
+                .pop_front()
+                .map(|(_, video_frame)| video_frame)
+                .unwrap_or_default()
+        } else {
+            VideoFrame::default()
+        };
+        self.entries
+            .push_back((timestamp, mem::replace(video_frame, spare)));
+    }
+}
+
+impl FrameSource {
+    pub(crate) fn new(
+        builder: ThumbnailerBuilder,
+        output_format: OutputFormat,
+        decoder: MovieDecoder,
+        (cache_capacity, cache_tolerance): (usize, Duration),
+    ) -> Self {
+        Self {
+            builder,
+            output_format,
+            state: Arc::new(Mutex::new(FrameSourceState {
+                decoder,
+                video_frame: VideoFrame::default(),
+                cache: FrameCache::new(cache_capacity, cache_tolerance),
+            })),
+        }
+    }
+
+    /// Seeks to `timestamp` and returns the frame found there, processed with the same options as
+    /// the `Thumbnailer` this source was opened from. A frame extracted earlier within
+    /// `ThumbnailerBuilder::frame_cache_tolerance` of `timestamp` is reused if still cached
+    pub async fn frame_at(
+        &mut self,
+        timestamp: Duration,
+    ) -> Result<OutputContainer, ThumbnailerError> {
+        let builder = self.builder.clone();
+        let output_format = self.output_format;
+        let state = Arc::clone(&self.state);
+
+        self.builder
+            .spawn_blocking(move || {
+                // A panic while holding the lock leaves the decoder in a state the next seek resets
+                let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+                let FrameSourceState {
+                    decoder,
+                    video_frame,
+                    cache,
+                } = &mut *state;
+                if let Some(cached) = cache.get(timestamp) {
+                    return builder.encode(cached, output_format);
+                }
+                decoder.seek(timestamp)?;
+
+                builder.extract_video_frame(decoder, video_frame)?;
+
+                let output = builder.encode(video_frame, output_format);
+                cache.insert(timestamp, video_frame);
+                output
+            })
+            .await?
+    }
+
+    /// Drops the frames and packets buffered by the decoder, which seeking already does, e.g. if
+    /// artifacts of frames decoded before a seek still show. Cached frames are kept
+    pub fn flush(&mut self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.decoder.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(width: u32) -> VideoFrame {
+        VideoFrame {
+            width,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn frame_cache_evicts_least_recently_used() {
+        let mut cache = FrameCache::new(2, Duration::from_millis(100));
+        cache.insert(Duration::from_secs(1), &mut frame(1));
+        cache.insert(Duration::from_secs(2), &mut frame(2));
+        assert_eq!(
+            cache.get(Duration::from_millis(1050)).map(|f| f.width),
+            Some(1)
+        );
+        assert!(cache.get(Duration::from_millis(1500)).is_none());
+
+        let mut third = frame(3);
+        cache.insert(Duration::from_secs(3), &mut third);
+        // The frame at 2s was the least recently used and its buffer is handed back
+        assert_eq!(third.width, 2);
+        assert!(cache.get(Duration::from_secs(2)).is_none());
+        assert!(cache.get(Duration::from_secs(1)).is_some());
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    MovieDecoder, OutputContainer, OutputFormat, ThumbnailerBuilder, ThumbnailerError, VideoFrame,
+};
+
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+/// `Keyframes` walks the key frames of a video file, obtained from
+/// `Thumbnailer::process_all_keyframes`, decoding and encoding each one only when it is requested
+/// so that long files never have all their thumbnails in memory at once.
+pub struct Keyframes {
+    builder: ThumbnailerBuilder,
+    output_format: OutputFormat,
+    state: Arc<Mutex<KeyframesState>>,
+}
+
+struct KeyframesState {
+    decoder: MovieDecoder,
+    // Scratch frame whose buffer is reused by every key frame
+    video_frame: VideoFrame,
+}
+
+impl Keyframes {
+    pub(crate) fn new(
+        builder: ThumbnailerBuilder,
+        output_format: OutputFormat,
+        decoder: MovieDecoder,
+    ) -> Self {
+        Self {
+            builder,
+            output_format,
+            state: Arc::new(Mutex::new(KeyframesState {
+                decoder,
+                video_frame: VideoFrame::default(),
+            })),
+        }
+    }
+
+    /// Decodes the next key frame and returns its position along with its thumbnail, processed
+    /// with the same options as the `Thumbnailer` it was obtained from, or `None` once the video
+    /// stream is exhausted
+    pub async fn next_keyframe(
+        &mut self,
+    ) -> Result<Option<(Duration, OutputContainer)>, ThumbnailerError> {
+        let builder = self.builder.clone();
+        let output_format = self.output_format;
+        let state = Arc::clone(&self.state);
+
+        self.builder
+            .spawn_blocking(move || {
+                let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+                let KeyframesState {
+                    decoder,
+                    video_frame,
+                } = &mut *state;
+                if !decoder.decode_next_key_frame()? {
+                    return Ok(None);
+                }
+                let timestamp = decoder.get_frame_timestamp().unwrap_or_default();
+
+                builder.extract_video_frame(decoder, video_frame)?;
+
+                let output = builder.encode(video_frame, output_format)?;
+                Ok(Some((timestamp, output)))
+            })
+            .await?
+    }
+}
@@ -1,48 +1,343 @@
-use crate::{
-    film_strip::film_strip_filter,
-    movie_decoder::{MovieDecoder, ThumbnailSize},
-    video_frame::VideoFrame,
-};
+use crate::{film_strip::film_strip_filter, movie_decoder::ThumbnailSize, video_frame::VideoFrame};
 
 use std::path::Path;
+#[cfg(feature = "fs")]
+use std::path::PathBuf;
 
+mod attachments;
+mod blank_frames;
+mod cue_sheet;
+mod encoder;
 mod error;
-mod film_strip;
+#[cfg(feature = "face-detect")]
+mod face_detection;
+pub mod film_strip;
+mod filters;
+mod frame_selector;
+mod frame_source;
+mod keyframes;
+mod logging;
+mod memory_input;
+mod metadata;
 mod movie_decoder;
+#[cfg(feature = "phash")]
+mod phash;
+mod sprite_sheet;
+mod streams;
 mod thumbnailer;
 mod utils;
 mod video_frame;
 
-pub use error::ThumbnailerError;
-pub use thumbnailer::{Thumbnailer, ThumbnailerBuilder};
+pub use attachments::{ArtType, Attachment, EmbeddedPicture};
+pub use blank_frames::{BlankFrameDetection, LuminanceStatistic};
+pub use cue_sheet::parse_cue_sheet;
+pub use encoder::Encoder;
+pub use error::{ErrorKind, ThumbnailerError};
+pub use filters::Corner;
+pub use frame_selector::FrameSelector;
+pub use frame_source::FrameSource;
+pub use keyframes::Keyframes;
+pub use logging::LogLevel;
+pub use movie_decoder::{DecodeStats, MovieDecoder, RotationHandling};
+pub use sprite_sheet::{SpriteSheet, SpriteSheetLayout};
+pub use streams::{DurationSource, StreamInfo};
+#[cfg(feature = "jpeg")]
+pub use thumbnailer::MJPEG_BOUNDARY;
+pub use thumbnailer::{FrameSelection, RangeInput, Thumbnailer, ThumbnailerBuilder};
+pub use video_frame::{Orientation, SourceMetadata, YuvFrame, YuvPlane};
 
-#[derive(Debug)]
+/// Supported output formats
+///
+/// The `quality` set on a `ThumbnailerBuilder` is always in the range [0.0, 100.0] and is mapped
+/// to each encoder's native scale by `OutputFormat::native_quality`:
+/// - `Webp`: passed through as libwebp's quality factor, 0.0 being the smallest file and 100.0
+///   the best quality
+/// - `Png`: lossless, the quality is ignored
+/// - `Jpeg`: rounded to the JPEG quality factor in the range [1, 100], 0.0 mapping to 1
+/// - `Raw`: not encoded, the quality is ignored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OutputFormat {
     #[cfg(feature = "webp")]
     Webp,
     #[cfg(feature = "png")]
     Png,
+    #[cfg(feature = "jpeg")]
+    Jpeg,
+    /// Scaled pixels, rows being tightly packed, without any container nor metadata, for
+    /// callers uploading them to their own texture pipeline or encoder. RGB24 top-down unless
+    /// set otherwise with `ThumbnailerBuilder::raw_channel_order` and `raw_row_order`
+    Raw,
+}
+
+/// Formats negotiated by `OutputFormat::from_accept_header`, most preferred first
+const ACCEPT_PREFERENCE: &[OutputFormat] = &[
+    #[cfg(feature = "webp")]
+    OutputFormat::Webp,
+    #[cfg(feature = "png")]
+    OutputFormat::Png,
+    #[cfg(feature = "jpeg")]
+    OutputFormat::Jpeg,
+];
+
+impl OutputFormat {
+    /// Best format enabled in this build for an HTTP `Accept` header, the one with the highest
+    /// `q` weight, ties going to WebP then PNG then JPEG. Each format is weighted by its most
+    /// specific media range, e.g. `image/png` over `image/*` over `*/*`. `None` if none is
+    /// acceptable. `Raw` is never chosen
+    pub fn from_accept_header(accept: &str) -> Option<OutputFormat> {
+        let media_ranges = accept
+            .split(',')
+            .filter_map(|media_range| {
+                let mut parameters = media_range.split(';');
+                let media_type = parameters.next()?.trim();
+                let weight = parameters
+                    .filter_map(|parameter| {
+                        let (name, value) = parameter.split_once('=')?;
+                        name.trim().eq_ignore_ascii_case("q").then_some(value)
+                    })
+                    .next()
+                    .map_or(Some(1.0), |value| value.trim().parse::<f32>().ok())?;
+                (!media_type.is_empty()).then_some((media_type, weight))
+            })
+            .collect::<Vec<_>>();
+
+        let weight_of = |format: OutputFormat| {
+            media_ranges
+                .iter()
+                .filter_map(|&(media_type, weight)| {
+                    let specificity = if media_type.eq_ignore_ascii_case(format.mime_type()) {
+                        2
+                    } else if media_type.eq_ignore_ascii_case("image/*") {
+                        1
+                    } else if media_type == "*/*" {
+                        0
+                    } else {
+                        return None;
+                    };
+                    Some((specificity, weight))
+                })
+                .max_by_key(|&(specificity, _)| specificity)
+                .map(|(_, weight)| weight)
+        };
+
+        ACCEPT_PREFERENCE
+            .iter()
+            .filter_map(|&format| Some((format, weight_of(format)?)))
+            .filter(|&(_, weight)| weight > 0.0)
+            .fold(None, |best, (format, weight)| match best {
+                Some((_, best_weight)) if best_weight >= weight => best,
+                _ => Some((format, weight)),
+            })
+            .map(|(format, _)| format)
+    }
+
+    /// Maps a quality in the range [0.0, 100.0] to the value handed to this format's encoder,
+    /// or `None` if the encoder doesn't take a quality at all
+    #[cfg_attr(not(any(feature = "webp", feature = "jpeg")), allow(unused_variables))]
+    pub fn native_quality(&self, quality: f32) -> Option<f32> {
+        match self {
+            #[cfg(feature = "webp")]
+            OutputFormat::Webp => Some(quality),
+            #[cfg(feature = "png")]
+            OutputFormat::Png => None,
+            #[cfg(feature = "jpeg")]
+            OutputFormat::Jpeg => Some(quality.round().clamp(1.0, 100.0)),
+            OutputFormat::Raw => None,
+        }
+    }
+
+    /// MIME type of images encoded in this format
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "webp")]
+            OutputFormat::Webp => "image/webp",
+            #[cfg(feature = "png")]
+            OutputFormat::Png => "image/png",
+            #[cfg(feature = "jpeg")]
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Raw => "application/octet-stream",
+        }
+    }
+
+    /// File extension of images encoded in this format, as recognized by `Thumbnailer::process`
+    pub fn extension(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "webp")]
+            OutputFormat::Webp => "webp",
+            #[cfg(feature = "png")]
+            OutputFormat::Png => "png",
+            #[cfg(feature = "jpeg")]
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Raw => "rgb",
+        }
+    }
+}
+
+/// Order of the channels of each pixel of `OutputFormat::Raw` outputs, alpha being always opaque
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ChannelOrder {
+    #[default]
+    Rgb,
+    Bgr,
+    Rgba,
+    Bgra,
+}
+
+impl ChannelOrder {
+    /// Bytes per pixel
+    pub fn channels(&self) -> usize {
+        match self {
+            ChannelOrder::Rgb | ChannelOrder::Bgr => 3,
+            ChannelOrder::Rgba | ChannelOrder::Bgra => 4,
+        }
+    }
+}
+
+/// Order of the rows of `OutputFormat::Raw` outputs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum RowOrder {
+    #[default]
+    TopDown,
+    /// Last row first, as expected by Direct3D and Windows bitmaps
+    BottomUp,
+}
+
+/// Format-agnostic quality, set with `ThumbnailerBuilder::quality_preset` and mapped to a
+/// `quality` suited to each output format by `QualityPreset::quality`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    /// Lossless encoding where the format supports it, the best quality otherwise
+    Lossless,
+}
+
+impl QualityPreset {
+    /// Quality in range [0.0, 100.0] this preset stands for when encoding to `output_format`.
+    /// WebP reaches a given fidelity at a lower quality factor than JPEG, hence lower values
+    #[cfg_attr(not(any(feature = "webp", feature = "jpeg")), allow(unused_variables))]
+    pub fn quality(&self, output_format: OutputFormat) -> f32 {
+        match (self, output_format) {
+            #[cfg(feature = "webp")]
+            (QualityPreset::Low, OutputFormat::Webp) => 40.0,
+            #[cfg(feature = "webp")]
+            (QualityPreset::Medium, OutputFormat::Webp) => 65.0,
+            #[cfg(feature = "webp")]
+            (QualityPreset::High, OutputFormat::Webp) => 85.0,
+            #[cfg(feature = "jpeg")]
+            (QualityPreset::Low, OutputFormat::Jpeg) => 50.0,
+            #[cfg(feature = "jpeg")]
+            (QualityPreset::Medium, OutputFormat::Jpeg) => 75.0,
+            #[cfg(feature = "jpeg")]
+            (QualityPreset::High, OutputFormat::Jpeg) => 90.0,
+            // Lossless WebP is encoded with the highest compression effort, while formats
+            // without a quality ignore it
+            _ => 100.0,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct OutputContainer {
     pub width: u32,
     pub height: u32,
+    /// Width of the source as decoded, see `display_dimensions` for the displayed one
     pub source_width: u32,
     pub source_height: u32,
+    /// Width and height of the source as decoded, before any crop, sample aspect ratio or
+    /// rotation, same as `source_width` and `source_height`
+    pub coded_dimensions: (u32, u32),
+    /// Width and height of the source as displayed, once cropped and with its sample aspect ratio
+    /// and rotation applied
+    pub display_dimensions: (u32, u32),
+    pub orientation: Option<Orientation>,
+    /// Whether the thumbnail was scaled up from the source, as displayed once cropped and with
+    /// its sample aspect ratio and rotation applied, e.g. to flag low resolution thumbnails
+    pub was_upscaled: bool,
+    pub source_codec: String,
+    pub source_pixel_format: String,
+    pub format: OutputFormat,
+    /// Quality in range [0.0, 100.0] the thumbnail was encoded with, below the configured one
+    /// when lowered by `ThumbnailerBuilder::target_max_bytes`. `None` for lossless formats and
+    /// with `ThumbnailerBuilder::raw_quality`
+    pub quality: Option<f32>,
+    /// Work it took to decode the thumbnail's frame
+    pub decode_stats: DecodeStats,
+    /// Perceptual hash of the thumbnail's frame, set only with `ThumbnailerBuilder::with_phash`
+    #[cfg(feature = "phash")]
+    pub phash: Option<u64>,
+    /// Average hash of the thumbnail's frame, set only with `ThumbnailerBuilder::with_ahash`
+    #[cfg(feature = "phash")]
+    pub ahash: Option<u64>,
     pub bytes: Vec<u8>,
 }
 
 impl OutputContainer {
-    fn from(video_frame: &VideoFrame, bytes: Vec<u8>) -> Self {
+    fn from(video_frame: &VideoFrame, format: OutputFormat, bytes: Vec<u8>) -> Self {
         Self {
             width: video_frame.width,
             height: video_frame.height,
             source_width: video_frame.source_width,
             source_height: video_frame.source_height,
+            coded_dimensions: video_frame.coded_dimensions,
+            display_dimensions: video_frame.display_dimensions,
+            orientation: video_frame.orientation,
+            was_upscaled: video_frame.was_upscaled,
+            source_codec: video_frame.source_codec.clone(),
+            source_pixel_format: video_frame.source_pixel_format.clone(),
+            format,
+            quality: None,
+            decode_stats: video_frame.decode_stats,
+            #[cfg(feature = "phash")]
+            phash: video_frame.phash,
+            #[cfg(feature = "phash")]
+            ahash: video_frame.ahash,
             bytes,
         }
     }
+
+    /// Encodes the thumbnail as a `data:` URL, e.g. to inline it in an HTML document
+    pub fn to_data_url(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.format.mime_type(),
+            utils::to_base64(&self.bytes)
+        )
+    }
+}
+
+/// Rough upper bound of the size in bytes of a `width`x`height` thumbnail encoded with `format`
+/// at `quality`, in the range [0.0, 100.0], e.g. to pre-allocate buffers or enforce size budgets
+/// before encoding. Embedded metadata, such as an ICC profile, comes on top of it
+pub fn estimate_output_size(
+    (width, height): (u32, u32),
+    format: OutputFormat,
+    quality: f32,
+) -> usize {
+    // Headers and chunks written whatever the picture
+    const CONTAINER_OVERHEAD: usize = 1024;
+
+    // Leaves room for an alpha channel
+    if format == OutputFormat::Raw {
+        return width as usize * height as usize * 4;
+    }
+    let image_size = match format.native_quality(quality) {
+        // Lossy encoders spend more bits per pixel as the quality increases, up to about two
+        // bytes per pixel for noisy pictures at the best quality
+        Some(quality) => {
+            let quality = quality.clamp(0.0, 100.0) / 100.0;
+            let bytes_per_pixel = 0.25 + 1.75 * quality * quality;
+            (width as f32 * height as f32 * bytes_per_pixel).ceil() as usize
+        }
+        // Lossless, so bounded by the raw rows, each preceded by its filter type, plus the few
+        // bytes deflate adds to every block it fails to compress
+        None => {
+            let raw_size = height as usize * (width as usize * 3 + 1);
+            raw_size + raw_size / 1000
+        }
+    };
+    image_size + CONTAINER_OVERHEAD
 }
 
 /// Helper function to generate a thumbnail file from a video file with reasonable defaults
@@ -61,6 +356,138 @@ pub async fn to_thumbnail(
         .await
 }
 
+/// Outcome of `to_thumbnails_dir` for each file of the input directory
+#[cfg(feature = "fs")]
+#[derive(Debug, Default)]
+pub struct DirectorySummary {
+    /// Paths of the thumbnails written
+    pub succeeded: Vec<PathBuf>,
+    /// Paths of the videos that couldn't be thumbnailed or directories that couldn't be read,
+    /// along with the reason
+    pub failed: Vec<(PathBuf, ThumbnailerError)>,
+    /// Paths of the files skipped as they aren't videos
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Extensions, compared case-insensitively, of the files `to_thumbnails_dir` treats as videos
+#[cfg(feature = "fs")]
+pub const VIDEO_EXTENSIONS: &[&str] = &[
+    "3g2", "3gp", "asf", "avi", "divx", "f4v", "flv", "m2ts", "m4v", "mkv", "mov", "mp4", "mpeg",
+    "mpg", "mts", "mxf", "ogv", "ts", "vob", "webm", "wmv",
+];
+
+/// Helper function to generate a thumbnail for every video of `input_dir` and its subdirectories
+/// with reasonable defaults. Thumbnails are written to the same relative paths in `output_dir`,
+/// with the extension of `output_format`, e.g. `clips/a.mp4` to `clips/a.webp`. Files are
+/// recognized as videos by their extension, listed in `VIDEO_EXTENSIONS`, others such as images
+/// or audio being skipped, along with videos without a video stream. A failure only fails its
+/// own file or directory
+#[cfg(feature = "fs")]
+pub async fn to_thumbnails_dir(
+    input_dir: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+    size: u32,
+    quality: f32,
+    output_format: OutputFormat,
+) -> Result<DirectorySummary, ThumbnailerError> {
+    let thumbnailer = ThumbnailerBuilder::new()
+        .size(size)
+        .quality(quality)?
+        .build();
+    let (input_dir, output_dir) = (input_dir.as_ref(), output_dir.as_ref());
+    // Both are canonicalized so that an output directory inside the input one is recognized
+    // however either is spelled, as it would otherwise get walked too
+    tokio::fs::create_dir_all(output_dir).await?;
+    let canonical_input_dir = tokio::fs::canonicalize(input_dir).await?;
+    let canonical_output_dir = tokio::fs::canonicalize(output_dir).await?;
+
+    // Paths are reported relative to `input_dir` as passed, rather than canonicalized
+    let reported_path = |canonical_path: &Path| {
+        input_dir.join(
+            canonical_path
+                .strip_prefix(&canonical_input_dir)
+                .unwrap_or(canonical_path),
+        )
+    };
+
+    let mut summary = DirectorySummary::default();
+    let mut directories = vec![canonical_input_dir.clone()];
+    while let Some(directory) = directories.pop() {
+        // An unreadable directory only fails itself, the rest of the walk goes on
+        let mut entries = match tokio::fs::read_dir(&directory).await {
+            Ok(entries) => entries,
+            Err(error) => {
+                summary
+                    .failed
+                    .push((reported_path(&directory), error.into()));
+                continue;
+            }
+        };
+        loop {
+            let entry = match entries.next_entry().await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(error) => {
+                    summary
+                        .failed
+                        .push((reported_path(&directory), error.into()));
+                    break;
+                }
+            };
+            let canonical_path = entry.path();
+            if canonical_path == canonical_output_dir {
+                continue;
+            }
+            let path = reported_path(&canonical_path);
+            match entry.file_type().await {
+                Ok(file_type) if file_type.is_dir() => {
+                    directories.push(canonical_path);
+                    continue;
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    summary.failed.push((path, error.into()));
+                    continue;
+                }
+            }
+
+            let is_video = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map_or(false, |extension| {
+                    VIDEO_EXTENSIONS
+                        .iter()
+                        .any(|video_extension| video_extension.eq_ignore_ascii_case(extension))
+                });
+            if !is_video {
+                summary.skipped.push(path);
+                continue;
+            }
+
+            let relative_path = path.strip_prefix(input_dir).unwrap_or(&path);
+            let output_path = output_dir
+                .join(relative_path)
+                .with_extension(output_format.extension());
+            if let Some(parent) = output_path.parent() {
+                if let Err(error) = tokio::fs::create_dir_all(parent).await {
+                    summary.failed.push((path, error.into()));
+                    continue;
+                }
+            }
+            match thumbnailer.process(&path, &output_path).await {
+                Ok(()) => summary.succeeded.push(output_path),
+                Err(
+                    ThumbnailerError::UnrecognizedFormat(_)
+                    | ThumbnailerError::EmptyInput
+                    | ThumbnailerError::Ffmpeg(error::FfmpegError::StreamNotFound),
+                ) => summary.skipped.push(path),
+                Err(error) => summary.failed.push((path, error)),
+            }
+        }
+    }
+    Ok(summary)
+}
+
 /// Helper function to generate a thumbnail file from a video file with reasonable defaults
 pub async fn to_thumbnail_bytes(
     video_file_path: impl AsRef<Path>,
@@ -125,12 +552,7 @@ mod tests {
     }
 
     async fn test_all_files(format: OutputFormat) {
-        let extension = match format {
-            #[cfg(feature = "webp")]
-            OutputFormat::Webp => "webp",
-            #[cfg(feature = "png")]
-            OutputFormat::Png => "png",
-        };
+        let extension = format.extension();
         let input_files = get_input_filenames()
             .clone()
             .into_iter()
@@ -170,4 +592,124 @@ mod tests {
     async fn test_all_files_png() {
         test_all_files(OutputFormat::Png).await;
     }
+
+    #[tokio::test]
+    #[cfg(feature = "webp")]
+    async fn estimate_output_size_bounds_samples() {
+        for input in get_input_filenames() {
+            let container = to_webp_bytes(Path::new("samples").join(input), 128, 100.0)
+                .await
+                .unwrap();
+            assert!(
+                container.bytes.len()
+                    <= estimate_output_size(
+                        (container.width, container.height),
+                        OutputFormat::Webp,
+                        100.0
+                    )
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "webp", feature = "png"))]
+    fn from_accept_header_negotiates() {
+        let chrome = "text/html,image/avif,image/webp,image/apng,image/*,*/*;q=0.8";
+        assert_eq!(
+            OutputFormat::from_accept_header(chrome),
+            Some(OutputFormat::Webp)
+        );
+        assert_eq!(
+            OutputFormat::from_accept_header("image/png, image/*;q=0.5"),
+            Some(OutputFormat::Png)
+        );
+        assert_eq!(
+            OutputFormat::from_accept_header("image/webp;q=0, image/*"),
+            Some(OutputFormat::Png)
+        );
+        assert_eq!(OutputFormat::from_accept_header("text/html"), None);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "png")]
+    async fn empty_input_is_rejected() {
+        let input_dir = tempdir().unwrap();
+        let path = input_dir.path().join("placeholder.mp4");
+        fs::write(&path, b"").await.unwrap();
+
+        assert!(matches!(
+            to_png_bytes(&path, 128).await,
+            Err(ThumbnailerError::EmptyInput)
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "webp")]
+    async fn to_thumbnails_dir_skips_non_videos() {
+        let input_dir = tempdir().unwrap();
+        fs::create_dir(input_dir.path().join("clips"))
+            .await
+            .unwrap();
+        fs::copy(
+            Path::new("samples").join("video_01.mp4"),
+            input_dir.path().join("clips").join("video_01.mp4"),
+        )
+        .await
+        .unwrap();
+        for non_video in ["notes.txt", "cover.png", "song.mp3"] {
+            fs::write(input_dir.path().join(non_video), "not a video")
+                .await
+                .unwrap();
+        }
+
+        let output_dir = tempdir().unwrap();
+        let summary = to_thumbnails_dir(
+            input_dir.path(),
+            output_dir.path(),
+            128,
+            80.0,
+            OutputFormat::Webp,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            summary.succeeded,
+            vec![output_dir.path().join("clips").join("video_01.webp")]
+        );
+        let mut skipped = summary.skipped;
+        skipped.sort();
+        assert_eq!(
+            skipped,
+            vec![
+                input_dir.path().join("cover.png"),
+                input_dir.path().join("notes.txt"),
+                input_dir.path().join("song.mp3"),
+            ]
+        );
+        assert!(summary.failed.is_empty());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "webp")]
+    async fn to_thumbnails_dir_skips_nested_output_dir() {
+        let root = tempdir().unwrap();
+        let input_dir = root.path().join("input");
+        fs::create_dir(&input_dir).await.unwrap();
+        fs::copy(
+            Path::new("samples").join("video_01.mp4"),
+            input_dir.join("video_01.mp4"),
+        )
+        .await
+        .unwrap();
+
+        // Spelled differently from the paths the input directory lists
+        let output_dir = input_dir.join("thumbs").join("small");
+        let input_dir = input_dir.join("..").join("input");
+        let summary = to_thumbnails_dir(&input_dir, &output_dir, 128, 80.0, OutputFormat::Webp)
+            .await
+            .unwrap();
+        assert_eq!(summary.succeeded, vec![output_dir.join("video_01.webp")]);
+        assert!(summary.skipped.is_empty());
+        assert!(summary.failed.is_empty());
+    }
 }
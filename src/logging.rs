@@ -0,0 +1,105 @@
+use ffmpeg_sys_next::{
+    av_log_set_level, AV_LOG_DEBUG, AV_LOG_ERROR, AV_LOG_FATAL, AV_LOG_INFO, AV_LOG_PANIC,
+    AV_LOG_QUIET, AV_LOG_TRACE, AV_LOG_VERBOSE, AV_LOG_WARNING,
+};
+
+/// Level of the messages FFmpeg logs while decoding, set process-wide with
+/// `ThumbnailerBuilder::ffmpeg_log_level`. Messages are written to stderr, or to the `tracing`
+/// facade under the `ffmpeg` target when the `tracing` feature is enabled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LogLevel {
+    /// Nothing is logged
+    #[default]
+    Quiet,
+    Panic,
+    Fatal,
+    Error,
+    Warning,
+    Info,
+    Verbose,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn as_ffmpeg_level(&self) -> i32 {
+        match self {
+            LogLevel::Quiet => AV_LOG_QUIET,
+            LogLevel::Panic => AV_LOG_PANIC,
+            LogLevel::Fatal => AV_LOG_FATAL,
+            LogLevel::Error => AV_LOG_ERROR,
+            LogLevel::Warning => AV_LOG_WARNING,
+            LogLevel::Info => AV_LOG_INFO,
+            LogLevel::Verbose => AV_LOG_VERBOSE,
+            LogLevel::Debug => AV_LOG_DEBUG,
+            LogLevel::Trace => AV_LOG_TRACE,
+        }
+    }
+}
+
+/// Sets FFmpeg's log level, which is global to the process
+pub(crate) fn set_log_level(level: LogLevel) {
+    #[cfg(feature = "tracing")]
+    {
+        static INSTALL_CALLBACK: std::sync::Once = std::sync::Once::new();
+        INSTALL_CALLBACK
+            .call_once(|| unsafe { ffmpeg_sys_next::av_log_set_callback(Some(log_to_tracing)) });
+    }
+
+    unsafe { av_log_set_level(level.as_ffmpeg_level()) };
+}
+
+/// Type of the `va_list` handed to log callbacks. bindgen emits `va_list` as a one element array
+/// on x86_64 System V, which decays to a pointer when passed, so callbacks take that pointer
+#[cfg(all(feature = "tracing", target_arch = "x86_64", not(windows)))]
+type VaList = *mut ffmpeg_sys_next::__va_list_tag;
+#[cfg(all(feature = "tracing", not(all(target_arch = "x86_64", not(windows)))))]
+type VaList = ffmpeg_sys_next::va_list;
+
+/// Log callback formatting FFmpeg's messages as its default callback does, then handing them to
+/// `tracing`
+#[cfg(feature = "tracing")]
+unsafe extern "C" fn log_to_tracing(
+    context: *mut std::ffi::c_void,
+    level: std::ffi::c_int,
+    format: *const std::ffi::c_char,
+    arguments: VaList,
+) {
+    use ffmpeg_sys_next::{av_log_format_line2, av_log_get_level};
+
+    if level > av_log_get_level() {
+        return;
+    }
+
+    let mut line = [0; 1024];
+    let mut print_prefix = 1;
+    if av_log_format_line2(
+        context,
+        level,
+        format,
+        arguments,
+        line.as_mut_ptr(),
+        line.len() as std::ffi::c_int,
+        &mut print_prefix,
+    ) < 0
+    {
+        return;
+    }
+    let line = std::ffi::CStr::from_ptr(line.as_ptr()).to_string_lossy();
+    let message = line.trim_end();
+    if message.is_empty() {
+        return;
+    }
+
+    if level <= AV_LOG_ERROR {
+        tracing::error!(target: "ffmpeg", "{message}");
+    } else if level <= AV_LOG_WARNING {
+        tracing::warn!(target: "ffmpeg", "{message}");
+    } else if level <= AV_LOG_INFO {
+        tracing::info!(target: "ffmpeg", "{message}");
+    } else if level <= AV_LOG_VERBOSE {
+        tracing::debug!(target: "ffmpeg", "{message}");
+    } else {
+        tracing::trace!(target: "ffmpeg", "{message}");
+    }
+}
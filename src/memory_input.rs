@@ -0,0 +1,91 @@
+use crate::error::FfmpegError;
+
+use ffmpeg_sys_next::{
+    av_free, av_malloc, avio_alloc_context, avio_context_free, AVIOContext, AVERROR_EOF,
+    AVSEEK_FORCE, AVSEEK_SIZE,
+};
+use std::{
+    ffi::{c_int, c_void},
+    io::{Cursor, Read, Seek, SeekFrom},
+};
+
+/// Size of the buffer FFmpeg reads into through the AVIO context
+const AVIO_BUFFER_SIZE: usize = 64 * 1024;
+
+/// In-memory input handed to FFmpeg through a custom, seekable AVIO context, for inputs that
+/// aren't files such as DASH segments
+pub(crate) struct MemoryInput {
+    avio_context: *mut AVIOContext,
+    // Boxed so that the pointer handed to FFmpeg as the opaque value stays valid when moved
+    _data: Box<Cursor<Vec<u8>>>,
+}
+
+impl MemoryInput {
+    pub(crate) fn new(data: Vec<u8>) -> Result<Self, FfmpegError> {
+        let mut data = Box::new(Cursor::new(data));
+
+        let buffer = unsafe { av_malloc(AVIO_BUFFER_SIZE) } as *mut u8;
+        if buffer.is_null() {
+            return Err(FfmpegError::IoContextAllocation);
+        }
+        let avio_context = unsafe {
+            avio_alloc_context(
+                buffer,
+                AVIO_BUFFER_SIZE as c_int,
+                0,
+                data.as_mut() as *mut Cursor<Vec<u8>> as *mut c_void,
+                Some(read_packet),
+                None,
+                Some(seek),
+            )
+        };
+        if avio_context.is_null() {
+            unsafe { av_free(buffer as *mut c_void) };
+            return Err(FfmpegError::IoContextAllocation);
+        }
+
+        Ok(Self {
+            avio_context,
+            _data: data,
+        })
+    }
+
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut AVIOContext {
+        self.avio_context
+    }
+}
+
+impl Drop for MemoryInput {
+    fn drop(&mut self) {
+        // The buffer may have been reallocated by FFmpeg, so the one of the context is freed
+        unsafe {
+            av_free((*self.avio_context).buffer as *mut c_void);
+            avio_context_free(&mut self.avio_context);
+        }
+    }
+}
+
+unsafe extern "C" fn read_packet(opaque: *mut c_void, buffer: *mut u8, size: c_int) -> c_int {
+    let data = &mut *(opaque as *mut Cursor<Vec<u8>>);
+    let buffer = std::slice::from_raw_parts_mut(buffer, size.max(0) as usize);
+    match data.read(buffer) {
+        Ok(0) | Err(_) => AVERROR_EOF,
+        Ok(read) => read as c_int,
+    }
+}
+
+unsafe extern "C" fn seek(opaque: *mut c_void, offset: i64, whence: c_int) -> i64 {
+    let data = &mut *(opaque as *mut Cursor<Vec<u8>>);
+    if whence & AVSEEK_SIZE != 0 {
+        return data.get_ref().len() as i64;
+    }
+
+    // `whence` is one of C's SEEK_SET, SEEK_CUR and SEEK_END
+    let position = match whence & !AVSEEK_FORCE {
+        0 => SeekFrom::Start(offset.max(0) as u64),
+        1 => SeekFrom::Current(offset),
+        2 => SeekFrom::End(offset),
+        _ => return -1,
+    };
+    data.seek(position).map_or(-1, |position| position as i64)
+}
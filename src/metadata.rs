@@ -0,0 +1,234 @@
+use crate::SourceMetadata;
+
+/// Exif orientation telling viewers to rotate 90° clockwise to display the image
+pub(crate) const EXIF_ROTATE_CLOCKWISE: u16 = 6;
+/// Exif orientation telling viewers to rotate 90° counterclockwise to display the image
+pub(crate) const EXIF_ROTATE_COUNTERCLOCKWISE: u16 = 8;
+/// Exif orientation telling viewers to rotate 180° to display the image
+pub(crate) const EXIF_ROTATE_HALF_TURN: u16 = 3;
+
+const EXIF_ORIENTATION_TAG: u16 = 0x0112;
+const TIFF_SHORT: u16 = 3;
+
+/// Namespace of the XMP properties that have no standard equivalent
+#[cfg(any(feature = "webp", feature = "jpeg"))]
+const XMP_NAMESPACE: &str = "https://crates.io/crates/ffmpegthumbnailer-rs/";
+/// Header preceding the XMP packet in a JPEG `APP1` segment
+#[cfg(feature = "jpeg")]
+pub(crate) const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// Name and version of the crate, recorded as the software that created thumbnails
+fn software() -> String {
+    format!("ffmpegthumbnailer-rs {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Keyword and text of the PNG text chunks describing the origin of a thumbnail
+#[cfg(feature = "png")]
+pub(crate) fn text_entries(source_metadata: &SourceMetadata) -> Vec<(&'static str, String)> {
+    let mut entries = Vec::with_capacity(3);
+    if let Some(source_path) = &source_metadata.source_path {
+        entries.push(("Source", source_path.to_string_lossy().into_owned()));
+    }
+    if let Some(seek_time) = source_metadata.seek_time {
+        entries.push(("Seek Time", format!("{:.3}", seek_time.as_secs_f64())));
+    }
+    entries.push(("Software", software()));
+    entries
+}
+
+/// Builds an XMP packet describing the origin of a thumbnail, as stored in WebP `XMP ` chunks
+/// and, after `JPEG_XMP_HEADER`, in JPEG `APP1` segments
+#[cfg(any(feature = "webp", feature = "jpeg"))]
+pub(crate) fn xmp_packet(source_metadata: &SourceMetadata) -> Vec<u8> {
+    let mut properties = format!(
+        "<xmp:CreatorTool>{}</xmp:CreatorTool>",
+        escape_xml(&software())
+    );
+    if let Some(source_path) = &source_metadata.source_path {
+        properties += &format!(
+            "<dc:source>{}</dc:source>",
+            escape_xml(&source_path.to_string_lossy())
+        );
+    }
+    if let Some(seek_time) = source_metadata.seek_time {
+        properties += &format!(
+            "<fftn:SeekTime>{:.3}</fftn:SeekTime>",
+            seek_time.as_secs_f64()
+        );
+    }
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\
+         <rdf:Description rdf:about=\"\" \
+         xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+         xmlns:fftn=\"{XMP_NAMESPACE}\">{properties}</rdf:Description>\
+         </rdf:RDF>\
+         </x:xmpmeta>\
+         <?xpacket end=\"r\"?>"
+    )
+    .into_bytes()
+}
+
+#[cfg(any(feature = "webp", feature = "jpeg"))]
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+/// Builds a minimal little-endian Exif (TIFF) block holding only the orientation tag, as stored
+/// in PNG `eXIf` and WebP `EXIF` chunks
+pub(crate) fn exif_orientation(orientation: u16) -> Vec<u8> {
+    let mut exif = Vec::with_capacity(26);
+    // TIFF header: byte order, magic number and offset of the first IFD
+    exif.extend_from_slice(b"II");
+    exif.extend_from_slice(&42u16.to_le_bytes());
+    exif.extend_from_slice(&8u32.to_le_bytes());
+    // IFD0 with a single entry, the value fitting in the entry itself
+    exif.extend_from_slice(&1u16.to_le_bytes());
+    exif.extend_from_slice(&EXIF_ORIENTATION_TAG.to_le_bytes());
+    exif.extend_from_slice(&TIFF_SHORT.to_le_bytes());
+    exif.extend_from_slice(&1u32.to_le_bytes());
+    exif.extend_from_slice(&orientation.to_le_bytes());
+    exif.extend_from_slice(&[0, 0]);
+    // No next IFD
+    exif.extend_from_slice(&0u32.to_le_bytes());
+    exif
+}
+
+/// Rewrites a WebP file produced by libwebp's simple encoder into the extended (`VP8X`) format,
+/// which is the only one able to carry `ICCP`, `EXIF` and `XMP ` chunks
+#[cfg(feature = "webp")]
+pub(crate) fn webp_with_metadata(
+    webp: &[u8],
+    width: u32,
+    height: u32,
+    icc_profile: Option<&[u8]>,
+    exif: Option<&[u8]>,
+    xmp: Option<&[u8]>,
+) -> Vec<u8> {
+    const VP8X_ICC_FLAG: u8 = 0x20;
+    const VP8X_EXIF_FLAG: u8 = 0x08;
+    const VP8X_XMP_FLAG: u8 = 0x04;
+
+    let metadata_size = icc_profile.map_or(0, <[u8]>::len)
+        + exif.map_or(0, <[u8]>::len)
+        + xmp.map_or(0, <[u8]>::len);
+    let mut chunks = Vec::with_capacity(webp.len() + metadata_size + 36);
+    let mut flags = 0;
+    // The color profile comes before the image data, the Exif and XMP metadata after
+    if let Some(icc_profile) = icc_profile {
+        flags |= VP8X_ICC_FLAG;
+        push_riff_chunk(&mut chunks, b"ICCP", icc_profile);
+    }
+    let mut offset = 12;
+    while offset + 8 <= webp.len() {
+        let fourcc = &webp[offset..offset + 4];
+        let size = u32::from_le_bytes([
+            webp[offset + 4],
+            webp[offset + 5],
+            webp[offset + 6],
+            webp[offset + 7],
+        ]) as usize;
+        let end = (offset + 8 + size + (size & 1)).min(webp.len());
+        if fourcc == b"VP8X" {
+            // Already extended, keep its flags but drop the header, rewritten below
+            flags |= webp.get(offset + 8).copied().unwrap_or_default()
+                & !(VP8X_ICC_FLAG | VP8X_EXIF_FLAG | VP8X_XMP_FLAG);
+        } else if fourcc != b"EXIF" && fourcc != b"ICCP" && fourcc != b"XMP " {
+            chunks.extend_from_slice(&webp[offset..end]);
+        }
+        offset = end;
+    }
+    if let Some(exif) = exif {
+        flags |= VP8X_EXIF_FLAG;
+        push_riff_chunk(&mut chunks, b"EXIF", exif);
+    }
+    if let Some(xmp) = xmp {
+        flags |= VP8X_XMP_FLAG;
+        push_riff_chunk(&mut chunks, b"XMP ", xmp);
+    }
+
+    let mut vp8x = Vec::with_capacity(10);
+    vp8x.extend_from_slice(&[flags, 0, 0, 0]);
+    vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+
+    let mut output = Vec::with_capacity(chunks.len() + 30);
+    output.extend_from_slice(b"RIFF");
+    output.extend_from_slice(&((4 + 18 + chunks.len()) as u32).to_le_bytes());
+    output.extend_from_slice(b"WEBP");
+    push_riff_chunk(&mut output, b"VP8X", &vp8x);
+    output.extend_from_slice(&chunks);
+    output
+}
+
+#[cfg(feature = "webp")]
+fn push_riff_chunk(output: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(fourcc);
+    output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    output.extend_from_slice(data);
+    // Chunks are padded to an even size
+    if data.len() % 2 == 1 {
+        output.push(0);
+    }
+}
+
+#[cfg(all(test, feature = "webp"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webp_with_metadata_layout() {
+        let mut simple = b"RIFF\x0e\x00\x00\x00WEBPVP8 \x02\x00\x00\x00ab".to_vec();
+        simple[4] = (simple.len() - 8) as u8;
+        let exif = exif_orientation(EXIF_ROTATE_CLOCKWISE);
+
+        let extended = webp_with_metadata(&simple, 640, 360, None, Some(&exif), None);
+        assert_eq!(&extended[..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes([extended[4], extended[5], extended[6], extended[7]]) as usize,
+            extended.len() - 8
+        );
+        assert_eq!(&extended[12..16], b"VP8X");
+        assert_eq!(extended[20], 0x08);
+        assert_eq!(&extended[24..30], &[0x7f, 0x02, 0x00, 0x67, 0x01, 0x00]);
+        assert_eq!(&extended[30..40], b"VP8 \x02\x00\x00\x00ab");
+        assert_eq!(&extended[40..44], b"EXIF");
+        assert_eq!(&extended[48..], &exif[..]);
+    }
+
+    #[test]
+    fn webp_with_metadata_puts_icc_profile_first() {
+        let mut simple = b"RIFF\x0e\x00\x00\x00WEBPVP8 \x02\x00\x00\x00ab".to_vec();
+        simple[4] = (simple.len() - 8) as u8;
+
+        let extended = webp_with_metadata(&simple, 640, 360, Some(b"icc"), None, None);
+        assert_eq!(extended[20], 0x20);
+        assert_eq!(&extended[30..42], b"ICCP\x03\x00\x00\x00icc\x00");
+        assert_eq!(&extended[42..], b"VP8 \x02\x00\x00\x00ab");
+    }
+
+    #[test]
+    fn xmp_packet_escapes_source_path() {
+        let xmp = xmp_packet(&SourceMetadata {
+            source_path: Some("a<b>&c.mkv".into()),
+            seek_time: Some(std::time::Duration::from_millis(1500)),
+        });
+        let xmp = String::from_utf8(xmp).unwrap();
+        assert!(xmp.contains("<dc:source>a&lt;b&gt;&amp;c.mkv</dc:source>"));
+        assert!(xmp.contains("<fftn:SeekTime>1.500</fftn:SeekTime>"));
+        assert!(xmp.ends_with("<?xpacket end=\"r\"?>"));
+    }
+}
@@ -1,39 +1,137 @@
 use crate::{
+    attachments::{self, ArtType},
     error::{FfmpegError, ThumbnailerError},
+    memory_input::MemoryInput,
+    metadata::{EXIF_ROTATE_CLOCKWISE, EXIF_ROTATE_COUNTERCLOCKWISE, EXIF_ROTATE_HALF_TURN},
     utils::from_path,
-    video_frame::{FfmpegFrame, FrameSource, VideoFrame},
+    video_frame::{
+        FfmpegFrame, FrameSource, Orientation, SourceMetadata, VideoFrame, YuvFrame, YuvPlane,
+    },
 };
 
 use ffmpeg_sys_next::{
-    av_buffersink_get_frame, av_buffersrc_write_frame, av_dict_get, av_display_rotation_get,
-    av_frame_alloc, av_frame_free, av_guess_sample_aspect_ratio, av_packet_alloc, av_packet_free,
-    av_packet_unref, av_read_frame, av_seek_frame, av_stream_get_side_data, avcodec_alloc_context3,
-    avcodec_find_decoder, avcodec_flush_buffers, avcodec_free_context, avcodec_open2,
-    avcodec_parameters_to_context, avcodec_receive_frame, avcodec_send_packet,
-    avfilter_get_by_name, avfilter_graph_alloc, avfilter_graph_config,
-    avfilter_graph_create_filter, avfilter_graph_free, avfilter_link, avformat_close_input,
-    avformat_find_stream_info, avformat_open_input, AVCodec, AVCodecContext, AVCodecID,
-    AVFilterContext, AVFilterGraph, AVFormatContext, AVFrame, AVMediaType, AVPacket,
-    AVPacketSideDataType, AVRational, AVStream, AVERROR, AVERROR_EOF, AV_DICT_IGNORE_SUFFIX,
-    AV_TIME_BASE, EAGAIN,
+    av_buffersink_get_frame, av_buffersrc_write_frame, av_dict_free, av_dict_get, av_dict_set,
+    av_display_rotation_get, av_frame_alloc, av_frame_free, av_frame_get_side_data, av_frame_ref,
+    av_frame_unref, av_get_pix_fmt_name, av_guess_format, av_guess_sample_aspect_ratio, av_log,
+    av_packet_alloc, av_packet_free, av_packet_unref, av_read_frame, av_seek_frame,
+    av_stream_get_side_data, avcodec_alloc_context3, avcodec_find_decoder, avcodec_flush_buffers,
+    avcodec_free_context, avcodec_get_name, avcodec_open2, avcodec_parameters_to_context,
+    avcodec_receive_frame, avcodec_send_packet, avfilter_get_by_name, avfilter_graph_alloc,
+    avfilter_graph_config, avfilter_graph_create_filter, avfilter_graph_free, avfilter_link,
+    avformat_alloc_context, avformat_close_input, avformat_find_stream_info, avformat_open_input,
+    AVCodec, AVCodecContext, AVCodecID, AVDictionary, AVDiscard, AVFilterContext, AVFilterGraph,
+    AVFormatContext, AVFrame, AVFrameSideDataType, AVIOInterruptCB, AVMediaType, AVPacket,
+    AVPacketSideDataType, AVPixelFormat, AVRational, AVStream, AVERROR, AVERROR_EOF,
+    AVERROR_INVALIDDATA, AVFMT_FLAG_CUSTOM_IO, AVSEEK_FLAG_BACKWARD, AV_DICT_IGNORE_SUFFIX,
+    AV_DISPOSITION_DEFAULT, AV_LOG_WARNING, AV_NOPTS_VALUE, AV_TIME_BASE, EAGAIN,
 };
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
 use std::{
-    ffi::{c_int, CString},
+    ffi::{c_char, c_int, c_void, CStr, CString},
     fmt::Write,
-    path::Path,
-    time::Duration,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 const AVERROR_EAGAIN: c_int = AVERROR(EAGAIN);
 
 #[derive(Debug, Clone, Copy)]
 pub(crate) enum ThumbnailSize {
-    Dimensions { width: u32, height: u32 },
+    Dimensions {
+        width: u32,
+        height: u32,
+    },
     Size(u32),
+    /// Displayed height, the width following the aspect ratio
+    Height(u32),
 }
 
-pub(crate) struct MovieDecoder {
+/// Work done by the decoder to produce a frame, counted since the input was opened, to spot the
+/// files that are expensive to thumbnail
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DecodeStats {
+    pub frames_decoded: u64,
+    pub packets_read: u64,
+    pub seeks: u32,
+    /// Time elapsed since the input was opened, including probing it
+    pub wall_time: Duration,
+}
+
+/// How the rotation stored in the video stream is applied to thumbnails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum RotationHandling {
+    /// Pixels are rotated, so that the thumbnail is displayed upright by any viewer
+    #[default]
+    Physical,
+    /// Pixels are kept as decoded and the rotation is written as an Exif orientation in the
+    /// encoded thumbnail, for viewers honoring it. The film strip, if any, is then drawn before
+    /// the rotation is applied
+    ExifTag,
+}
+
+/// Fractions of the displayed picture trimmed from each of its edges before scaling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Crop {
+    pub(crate) top: f32,
+    pub(crate) bottom: f32,
+    pub(crate) left: f32,
+    pub(crate) right: f32,
+}
+
+impl Crop {
+    /// Whether every fraction is in [0.0, 1.0) and a region remains in both directions
+    pub(crate) fn is_valid(&self) -> bool {
+        [self.top, self.bottom, self.left, self.right]
+            .iter()
+            .all(|fraction| (0.0..1.0).contains(fraction))
+            && self.top + self.bottom < 1.0
+            && self.left + self.right < 1.0
+    }
+}
+
+/// Options tweaking how a `MovieDecoder` opens and filters a video
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DecoderOptions {
+    pub(crate) prefer_embedded_metadata: bool,
+    pub(crate) burn_subtitles: bool,
+    pub(crate) max_source_dimension: Option<u32>,
+    pub(crate) rotation_handling: RotationHandling,
+    pub(crate) crop: Option<Crop>,
+    pub(crate) network_retries: u32,
+    pub(crate) network_timeout: Option<Duration>,
+    /// Time allowed to open and probe the input, network connection included
+    pub(crate) open_timeout: Option<Duration>,
+    pub(crate) black_bar_threshold: Option<u8>,
+    pub(crate) preserve_icc: bool,
+    pub(crate) embed_metadata: bool,
+    pub(crate) denoise_strength: Option<f32>,
+    /// Whether the conversion to the output pixel format is done at full precision with error
+    /// diffusion dithering
+    pub(crate) dither: bool,
+    /// Frames decoded at most past the key frame by `seek_exact` before giving up on the exact
+    /// position
+    pub(crate) max_seek_decode_frames: Option<u32>,
+    /// Duration trusted instead of the one of the input, whose streams are then only probed when
+    /// the container header doesn't describe them
+    pub(crate) assume_duration: Option<Duration>,
+    /// Kind of embedded picture used over the first one when there are several
+    pub(crate) preferred_art_type: Option<ArtType>,
+    /// File descriptor read through FFmpeg's `fd:` protocol, the input path being then `fd:`
+    #[cfg(unix)]
+    pub(crate) input_fd: Option<RawFd>,
+    /// Size of the frames returned by `MovieDecoder::thumbnail_frame`, the source size if none
+    pub(crate) thumbnail_size: Option<ThumbnailSize>,
+    pub(crate) maintain_aspect_ratio: bool,
+}
+
+/// Video opened for decoding, handed to a custom `FrameSelector` to move to the frame of its
+/// choice
+pub struct MovieDecoder {
+    options: DecoderOptions,
+    input_file: PathBuf,
     video_stream_index: i32,
+    subtitle_stream: Option<i32>,
     format_context: *mut AVFormatContext,
     video_codec_context: *mut AVCodecContext,
     video_codec: *const AVCodec,
@@ -44,13 +142,27 @@ pub(crate) struct MovieDecoder {
     frame: *mut AVFrame,
     packet: *mut AVPacket,
     allow_seek: bool,
+    is_still_image: bool,
+    is_animated_image: bool,
     use_embedded_data: bool,
+    active_area: Option<(i32, i32, i32, i32)>,
+    // Rotation of the video stream, read once as reading it may log a warning
+    rotation: i32,
+    opened_at: Instant,
+    // Whether reading packets stopped, at the end of the input or on a read error, since the last
+    // seek
+    end_of_input: bool,
+    decode_stats: DecodeStats,
+    // Custom I/O the format context reads from, which must outlive it
+    memory_input: Option<MemoryInput>,
+    // End of the open timeout, read by the interrupt callback of the format context while opening
+    open_deadline: Option<Box<Instant>>,
 }
 
 impl MovieDecoder {
     pub(crate) fn new(
         filename: impl AsRef<Path>,
-        prefer_embedded_metadata: bool,
+        options: DecoderOptions,
     ) -> Result<Self, ThumbnailerError> {
         let filename = filename.as_ref();
 
@@ -59,12 +171,53 @@ impl MovieDecoder {
         } else {
             filename
         };
-        let allow_seek = filename != Path::new("-")
-            && !filename.starts_with("rsts://")
-            && !filename.starts_with("udp://");
+        // Pipes, whether stdin as "-" or read through FFmpeg's `pipe:` protocol, can't be seeked
+        let is_pipe = filename == Path::new("-")
+            || filename
+                .to_str()
+                .map_or(false, |filename| filename.starts_with("pipe:"));
+        let allow_seek =
+            !is_pipe && !filename.starts_with("rsts://") && !filename.starts_with("udp://");
+
+        // Zero-byte files, such as upload placeholders, would only fail in FFmpeg's probing with
+        // an obscure error. Anything else than a local file is left to FFmpeg
+        if let Ok(metadata) = std::fs::metadata(filename) {
+            if metadata.is_file() && metadata.len() == 0 {
+                return Err(ThumbnailerError::EmptyInput);
+            }
+        }
 
+        Self::open(input_file, allow_seek, options, None)
+    }
+
+    /// Opens a video held in memory, the format being probed from its content. Subtitles can't
+    /// be burned from it, the `subtitles` filter reading them from a file
+    pub(crate) fn from_memory(
+        data: Vec<u8>,
+        options: DecoderOptions,
+    ) -> Result<Self, ThumbnailerError> {
+        if data.is_empty() {
+            return Err(ThumbnailerError::EmptyInput);
+        }
+        let memory_input = MemoryInput::new(data)?;
+        let options = DecoderOptions {
+            burn_subtitles: false,
+            ..options
+        };
+        Self::open(Path::new(""), true, options, Some(memory_input))
+    }
+
+    fn open(
+        input_file: &Path,
+        allow_seek: bool,
+        options: DecoderOptions,
+        memory_input: Option<MemoryInput>,
+    ) -> Result<Self, ThumbnailerError> {
         let mut decoder = Self {
+            options,
+            input_file: input_file.to_path_buf(),
             video_stream_index: -1,
+            subtitle_stream: None,
             format_context: std::ptr::null_mut(),
             video_codec_context: std::ptr::null_mut(),
             video_codec: std::ptr::null_mut(),
@@ -75,22 +228,72 @@ impl MovieDecoder {
             frame: std::ptr::null_mut(),
             packet: std::ptr::null_mut(),
             allow_seek,
+            is_still_image: false,
+            is_animated_image: false,
             use_embedded_data: false,
+            active_area: None,
+            rotation: -1,
+            opened_at: Instant::now(),
+            end_of_input: false,
+            decode_stats: DecodeStats::default(),
+            memory_input,
+            open_deadline: None,
         };
 
         unsafe {
+            if let Some(memory_input) = &mut decoder.memory_input {
+                decoder.format_context = avformat_alloc_context();
+                if decoder.format_context.is_null() {
+                    return Err(FfmpegError::IoContextAllocation.into());
+                }
+                (*decoder.format_context).pb = memory_input.as_mut_ptr();
+                (*decoder.format_context).flags |= AVFMT_FLAG_CUSTOM_IO;
+            }
+            if let Some(open_timeout) = decoder.options.open_timeout {
+                if decoder.format_context.is_null() {
+                    decoder.format_context = avformat_alloc_context();
+                    if decoder.format_context.is_null() {
+                        return Err(FfmpegError::IoContextAllocation.into());
+                    }
+                }
+                let deadline = Box::new(Instant::now() + open_timeout);
+                (*decoder.format_context).interrupt_callback = AVIOInterruptCB {
+                    callback: Some(interrupt_after_deadline),
+                    opaque: &*deadline as *const Instant as *mut c_void,
+                };
+                decoder.open_deadline = Some(deadline);
+            }
+
             let input_file_cstring = from_path(input_file)?;
-            match avformat_open_input(
+            let mut input_options = decoder.input_options();
+            let result = avformat_open_input(
                 &mut decoder.format_context,
                 input_file_cstring.as_ptr(),
                 std::ptr::null_mut(),
-                std::ptr::null_mut(),
-            ) {
+                &mut input_options,
+            );
+            // Options left in the dictionary are the ones not used by the protocol
+            av_dict_free(&mut input_options);
+            decoder.check_open_timeout(result)?;
+
+            match result {
                 0 => {
-                    check_error(
-                        avformat_find_stream_info(decoder.format_context, std::ptr::null_mut()),
-                        "Failed to get stream info",
-                    )?;
+                    // Probing decodes the start of every stream, which is only needed for the
+                    // duration when the header already gives the video dimensions and format
+                    if decoder.options.assume_duration.is_none()
+                        || !decoder.header_describes_video()
+                    {
+                        let result =
+                            avformat_find_stream_info(decoder.format_context, std::ptr::null_mut());
+                        decoder.check_open_timeout(result)?;
+                        check_error(result, "Failed to get stream info")?;
+                    }
+                }
+                // No demuxer recognized the data, e.g. for a text file
+                AVERROR_INVALIDDATA => {
+                    return Err(ThumbnailerError::UnrecognizedFormat(guess_format(
+                        &input_file_cstring,
+                    )))
                 }
                 e => {
                     return Err(ThumbnailerError::FfmpegWithReason(
@@ -99,9 +302,17 @@ impl MovieDecoder {
                     ))
                 }
             }
+            // The open timeout doesn't apply to decoding
+            if decoder.open_deadline.is_some() {
+                (*decoder.format_context).interrupt_callback.callback = None;
+            }
         }
 
-        decoder.initialize_video(prefer_embedded_metadata)?;
+        decoder.is_still_image = decoder.input_is_still_image();
+        decoder.is_animated_image = decoder.input_is_animated_image();
+        decoder.initialize_video()?;
+        decoder.rotation = decoder.read_stream_rotation();
+        decoder.subtitle_stream = decoder.find_subtitle_stream();
 
         decoder.frame = unsafe { av_frame_alloc() };
         if decoder.frame.is_null() {
@@ -111,7 +322,51 @@ impl MovieDecoder {
         Ok(decoder)
     }
 
-    pub(crate) fn decode_video_frame(&mut self) -> Result<(), ThumbnailerError> {
+    /// Fails with `ThumbnailerError::OpenTimeout` if FFmpeg returned the error `result` after the
+    /// open timeout, which the interrupt callback aborted
+    fn check_open_timeout(&self, result: c_int) -> Result<(), ThumbnailerError> {
+        match (&self.open_deadline, self.options.open_timeout) {
+            (Some(deadline), Some(open_timeout)) if result < 0 && Instant::now() >= **deadline => {
+                Err(ThumbnailerError::OpenTimeout(open_timeout))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Options of FFmpeg's network and `fd:` protocols, only set when configured so that local
+    /// files are opened the same as always
+    fn input_options(&self) -> *mut AVDictionary {
+        let mut options = std::ptr::null_mut();
+        let mut set = |key: &str, value: String| {
+            let key = CString::new(key).unwrap();
+            let value = CString::new(value).unwrap();
+            unsafe { av_dict_set(&mut options, key.as_ptr(), value.as_ptr(), 0) };
+        };
+
+        if self.options.network_retries > 0 {
+            set("reconnect", "1".to_string());
+            set("reconnect_streamed", "1".to_string());
+            set("reconnect_on_network_error", "1".to_string());
+            set("reconnect_on_http_error", "5xx".to_string());
+            set(
+                "reconnect_max_retries",
+                self.options.network_retries.to_string(),
+            );
+        }
+        if let Some(timeout) = self.options.network_timeout {
+            // Expressed in microseconds
+            set("timeout", timeout.as_micros().to_string());
+        }
+        #[cfg(unix)]
+        if let Some(fd) = self.options.input_fd {
+            set("fd", fd.to_string());
+        }
+
+        options
+    }
+
+    /// Decodes the next frame of the video stream
+    pub fn decode_video_frame(&mut self) -> Result<(), ThumbnailerError> {
         let mut frame_finished = false;
 
         while !frame_finished && self.get_video_packet() {
@@ -125,22 +380,95 @@ impl MovieDecoder {
         Ok(())
     }
 
+    /// Keeps decoding from the current position until a key frame is decoded, giving up after
+    /// as many attempts as seeking does
+    pub(crate) fn decode_until_key_frame(&mut self) -> Result<(), ThumbnailerError> {
+        let mut key_frame_attempts = 0;
+        while unsafe { (*self.frame).key_frame } == 0 {
+            key_frame_attempts += 1;
+            if key_frame_attempts >= 200 {
+                return Err(ThumbnailerError::FrameDecodeError);
+            }
+            self.decode_video_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Makes the decoder drop every frame but key frames, which is much faster when only those
+    /// are wanted
+    pub(crate) fn skip_non_key_frames(&mut self) {
+        unsafe { (*self.video_codec_context).skip_frame = AVDiscard::AVDISCARD_NONKEY };
+    }
+
+    /// Decodes forward up to the next key frame, returning false once the video stream is
+    /// exhausted
+    pub(crate) fn decode_next_key_frame(&mut self) -> Result<bool, ThumbnailerError> {
+        let mut draining = false;
+        loop {
+            let got_frame = if !draining && self.get_video_packet() {
+                self.decode_video_packet()?
+            } else {
+                // Frames still buffered in the decoder are only output once it is drained
+                if !draining {
+                    unsafe { avcodec_send_packet(self.video_codec_context, std::ptr::null()) };
+                    draining = true;
+                }
+                match unsafe { avcodec_receive_frame(self.video_codec_context, self.frame) } {
+                    0 => {
+                        self.decode_stats.frames_decoded += 1;
+                        true
+                    }
+                    _ => return Ok(false),
+                }
+            };
+            if got_frame && unsafe { (*self.frame).key_frame } != 0 {
+                return Ok(true);
+            }
+        }
+    }
+
     pub(crate) fn embedded_metadata_is_available(&self) -> bool {
         self.use_embedded_data
     }
 
-    pub(crate) fn seek(&mut self, seconds: i64) -> Result<(), ThumbnailerError> {
+    /// Drops the frames and packets buffered by the decoder, as done by every seek, so that none
+    /// decoded before is output again. Decoding then resumes at the next key frame
+    pub fn flush(&mut self) {
+        unsafe {
+            avcodec_flush_buffers(self.video_codec_context);
+            if !self.packet.is_null() {
+                av_packet_unref(self.packet);
+            }
+        }
+        self.end_of_input = false;
+    }
+
+    /// Seeks to the first key frame at or after `position`, which is fast but may land a few
+    /// seconds past it
+    pub fn seek(&mut self, position: Duration) -> Result<(), ThumbnailerError> {
+        // The single frame of an image is the one decoded when opening it
+        if self.is_still_image {
+            return Ok(());
+        }
+
         if !self.allow_seek {
             return Err(ThumbnailerError::SeekNotAllowed);
         }
 
-        let timestamp = (AV_TIME_BASE as i64).checked_mul(seconds).unwrap_or(0);
+        // Frames of animated images are drawn over the previous ones, only the first being a key
+        // frame, so they must be decoded in order up to the position
+        if self.is_animated_image {
+            return self.seek_exact(position);
+        }
+
+        let timestamp = (position.as_secs_f64() * AV_TIME_BASE as f64) as i64;
 
         check_error(
             unsafe { av_seek_frame(self.format_context, -1, timestamp, 0) },
             "Seeking video failed",
         )?;
-        unsafe { avcodec_flush_buffers(self.video_codec_context) };
+        self.decode_stats.seeks += 1;
+        self.flush();
 
         let mut key_frame_attempts = 0;
         let mut got_frame;
@@ -165,18 +493,243 @@ impl MovieDecoder {
         }
 
         if !got_frame {
+            // Running out of packets before the end announced by the container
+            if self.end_of_input && position < self.get_video_duration() {
+                return Err(ThumbnailerError::TruncatedFile);
+            }
             return Err(ThumbnailerError::SeekError);
         }
 
         Ok(())
     }
 
+    /// Seeks to the last key frame before `position` then decodes forward up to the frame displayed
+    /// at `position`, or the last frame of the stream when it ends before, for positions close to
+    /// the end where key frames are sparse. Stops early on the frame `max_seek_decode_frames` past
+    /// the key frame, when set
+    pub fn seek_exact(&mut self, position: Duration) -> Result<(), ThumbnailerError> {
+        if self.is_still_image {
+            return Ok(());
+        }
+
+        if !self.allow_seek {
+            return Err(ThumbnailerError::SeekNotAllowed);
+        }
+
+        let timestamp = (position.as_secs_f64() * AV_TIME_BASE as f64) as i64;
+        check_error(
+            unsafe { av_seek_frame(self.format_context, -1, timestamp, AVSEEK_FLAG_BACKWARD) },
+            "Seeking video failed",
+        )?;
+        self.decode_stats.seeks += 1;
+        self.flush();
+
+        let (time_base, start_time) = unsafe {
+            (
+                (*self.video_stream).time_base,
+                (*self.video_stream).start_time,
+            )
+        };
+        let start_time = if start_time == AV_NOPTS_VALUE {
+            0
+        } else {
+            start_time
+        };
+        let target = start_time
+            + (position.as_secs_f64() * time_base.den as f64 / time_base.num.max(1) as f64) as i64;
+
+        let mut last_frame = FfmpegFrame::new()?;
+        let mut has_last_frame = false;
+        let mut draining = false;
+        let mut frames_past_key_frame = 0;
+        loop {
+            let got_frame = if !draining && self.get_video_packet() {
+                self.decode_video_packet()?
+            } else {
+                // Frames still buffered in the decoder are only output once it is drained
+                if !draining {
+                    unsafe { avcodec_send_packet(self.video_codec_context, std::ptr::null()) };
+                    draining = true;
+                }
+                match unsafe { avcodec_receive_frame(self.video_codec_context, self.frame) } {
+                    0 => {
+                        self.decode_stats.frames_decoded += 1;
+                        true
+                    }
+                    _ => break,
+                }
+            };
+            if !got_frame {
+                continue;
+            }
+
+            if unsafe { (*self.frame).best_effort_timestamp } >= target {
+                return Ok(());
+            }
+            if self
+                .options
+                .max_seek_decode_frames
+                .map_or(false, |max_frames| frames_past_key_frame >= max_frames)
+            {
+                return Ok(());
+            }
+            frames_past_key_frame += 1;
+            unsafe {
+                av_frame_unref(last_frame.as_mut_ptr());
+                av_frame_ref(last_frame.as_mut_ptr(), self.frame);
+            }
+            has_last_frame = true;
+        }
+
+        if !has_last_frame {
+            return Err(ThumbnailerError::SeekError);
+        }
+        unsafe {
+            av_frame_unref(self.frame);
+            av_frame_ref(self.frame, last_frame.as_mut_ptr());
+        }
+        Ok(())
+    }
+
+    /// Current frame scaled to the thumbnail size set on the `ThumbnailerBuilder`
+    pub fn thumbnail_frame(&mut self) -> Result<VideoFrame, ThumbnailerError> {
+        let mut video_frame = VideoFrame::default();
+        self.get_scaled_video_frame(
+            self.options.thumbnail_size,
+            self.options.maintain_aspect_ratio,
+            &mut video_frame,
+        )?;
+        Ok(video_frame)
+    }
+
     pub(crate) fn get_scaled_video_frame(
         &mut self,
         scaled_size: Option<ThumbnailSize>,
         maintain_aspect_ratio: bool,
         video_frame: &mut VideoFrame,
     ) -> Result<(), ThumbnailerError> {
+        video_frame.icc_profile = if self.options.preserve_icc {
+            self.get_icc_profile()
+        } else {
+            None
+        };
+        video_frame.source_metadata = self.options.embed_metadata.then(|| SourceMetadata {
+            source_path: (!self.input_file.as_os_str().is_empty()).then(|| self.input_file.clone()),
+            seek_time: self.get_frame_timestamp(),
+        });
+        let mut new_frame = self.get_filtered_frame(scaled_size, maintain_aspect_ratio, "rgb24")?;
+
+        video_frame.width = unsafe { (*new_frame.as_mut_ptr()).width as u32 };
+        video_frame.height = unsafe { (*new_frame.as_mut_ptr()).height as u32 };
+        video_frame.line_size = unsafe { (*new_frame.as_mut_ptr()).linesize[0] as u32 };
+        video_frame.source_width = unsafe { (*self.video_codec_context).width } as u32;
+        video_frame.source_height = unsafe { (*self.video_codec_context).height } as u32;
+        video_frame.source = if self.use_embedded_data {
+            Some(FrameSource::Metadata)
+        } else {
+            Some(FrameSource::VideoStream)
+        };
+        video_frame.timestamp = if self.use_embedded_data {
+            None
+        } else {
+            self.get_frame_timestamp()
+        };
+        video_frame.source_codec = self.get_codec_name();
+        video_frame.source_pixel_format = self.get_pixel_format_name();
+        video_frame.decode_stats = self.get_decode_stats();
+        let (display_width, display_height) = self.get_display_dimensions();
+        video_frame.coded_dimensions = (video_frame.source_width, video_frame.source_height);
+        video_frame.display_dimensions = (display_width, display_height);
+        video_frame.orientation = Some(Orientation::from_dimensions(display_width, display_height));
+        // Compared by area, the frame being left unrotated with `RotationHandling::ExifTag`
+        video_frame.was_upscaled = video_frame.width as u64 * video_frame.height as u64
+            > display_width as u64 * display_height as u64;
+        video_frame.exif_orientation = match self.options.rotation_handling {
+            RotationHandling::Physical => None,
+            RotationHandling::ExifTag => match self.get_stream_rotation() {
+                1 => Some(EXIF_ROTATE_CLOCKWISE),
+                2 => Some(EXIF_ROTATE_COUNTERCLOCKWISE),
+                3 => Some(EXIF_ROTATE_HALF_TURN),
+                _ => None,
+            },
+        };
+
+        let frame_data_size = video_frame.line_size as usize * video_frame.height as usize;
+        match video_frame.data.capacity() {
+            0 => {
+                video_frame.data = Vec::with_capacity(frame_data_size);
+            }
+            c if c < frame_data_size => {
+                video_frame.data.reserve_exact(frame_data_size - c);
+                video_frame.data.clear();
+            }
+            c if c > frame_data_size => {
+                video_frame.data.shrink_to(frame_data_size);
+                video_frame.data.clear();
+            }
+            _ => {
+                video_frame.data.clear();
+            }
+        }
+
+        video_frame.data.extend_from_slice(unsafe {
+            std::slice::from_raw_parts((*new_frame.as_mut_ptr()).data[0], frame_data_size)
+        });
+
+        Ok(())
+    }
+
+    pub(crate) fn get_scaled_yuv_frame(
+        &mut self,
+        scaled_size: Option<ThumbnailSize>,
+        maintain_aspect_ratio: bool,
+    ) -> Result<YuvFrame, ThumbnailerError> {
+        let mut new_frame =
+            self.get_filtered_frame(scaled_size, maintain_aspect_ratio, "yuv420p")?;
+        let frame = new_frame.as_mut_ptr();
+
+        let width = unsafe { (*frame).width as u32 };
+        let height = unsafe { (*frame).height as u32 };
+        // YUV420 chroma planes are subsampled by 2 in both directions, rounding up
+        let chroma_height = (height + 1) / 2;
+
+        let plane = |index: usize, rows: u32| {
+            let stride = unsafe { (*frame).linesize[index] as u32 };
+            YuvPlane {
+                stride,
+                data: unsafe {
+                    std::slice::from_raw_parts(
+                        (*frame).data[index],
+                        stride as usize * rows as usize,
+                    )
+                }
+                .to_vec(),
+            }
+        };
+
+        Ok(YuvFrame {
+            width,
+            height,
+            y: plane(0, height),
+            u: plane(1, chroma_height),
+            v: plane(2, chroma_height),
+        })
+    }
+
+    fn get_filtered_frame(
+        &mut self,
+        scaled_size: Option<ThumbnailSize>,
+        maintain_aspect_ratio: bool,
+        pixel_format: &str,
+    ) -> Result<FfmpegFrame, ThumbnailerError> {
+        if !self.use_embedded_data {
+            self.update_frame_rotation();
+        }
+        self.active_area = match self.options.black_bar_threshold {
+            Some(threshold) if !self.use_embedded_data => self.detect_active_area(threshold),
+            _ => None,
+        };
+
         self.initialize_filter_graph(
             unsafe {
                 &(*(*(*self.format_context)
@@ -186,6 +739,7 @@ impl MovieDecoder {
             },
             scaled_size,
             maintain_aspect_ratio,
+            pixel_format,
         )?;
 
         check_error(
@@ -212,56 +766,359 @@ impl MovieDecoder {
             ));
         }
 
-        video_frame.width = unsafe { (*new_frame.as_mut_ptr()).width as u32 };
-        video_frame.height = unsafe { (*new_frame.as_mut_ptr()).height as u32 };
-        video_frame.line_size = unsafe { (*new_frame.as_mut_ptr()).linesize[0] as u32 };
-        video_frame.source_width = unsafe { (*self.video_codec_context).width } as u32;
-        video_frame.source_height = unsafe { (*self.video_codec_context).height } as u32;
-        video_frame.source = if self.use_embedded_data {
-            Some(FrameSource::Metadata)
+        unsafe { avfilter_graph_free(&mut self.filter_graph) };
+
+        Ok(new_frame)
+    }
+
+    /// Dimensions of the video as displayed, the sample aspect ratio being applied before the
+    /// rotation
+    pub(crate) fn get_display_dimensions(&self) -> (u32, u32) {
+        let (width, height) = self.get_cropped_dimensions();
+        let (mut width, height) = (width as u32, height as u32);
+
+        let aspect_ratio = unsafe {
+            av_guess_sample_aspect_ratio(self.format_context, self.video_stream, self.frame)
+        };
+        if aspect_ratio.num > 0 && aspect_ratio.den > 0 {
+            width = (width as u64 * aspect_ratio.num as u64 / aspect_ratio.den as u64) as u32;
+        }
+
+        // Transposing rotations (90° either way) swap the dimensions
+        match self.get_stream_rotation() {
+            1 | 2 => (height, width),
+            _ => (width, height),
+        }
+    }
+
+    /// Crop rectangle `(x, y, width, height)` in the decoded frame, the crop fractions being
+    /// relative to the displayed picture, hence rotated back to the frame orientation
+    fn get_crop_rectangle(&self) -> Option<(i32, i32, i32, i32)> {
+        if self.use_embedded_data || (self.options.crop.is_none() && self.active_area.is_none()) {
+            return None;
+        }
+
+        let Crop {
+            top,
+            bottom,
+            left,
+            right,
+        } = self.options.crop.unwrap_or(Crop {
+            top: 0.0,
+            bottom: 0.0,
+            left: 0.0,
+            right: 0.0,
+        });
+        // Fractions to trim from the top, bottom, left and right edges of the decoded frame
+        let (top, bottom, left, right) = match self.get_stream_rotation() {
+            1 => (right, left, top, bottom),
+            2 => (left, right, bottom, top),
+            3 => (bottom, top, right, left),
+            _ => (top, bottom, left, right),
+        };
+
+        // Fractions are relative to the picture left once black bars are removed
+        let (area_x, area_y, width, height) = self.active_area.unwrap_or_else(|| unsafe {
+            (
+                0,
+                0,
+                (*self.video_codec_context).width,
+                (*self.video_codec_context).height,
+            )
+        });
+        let (width, height) = (width as f32, height as f32);
+        let x = area_x + (width * left).round() as i32;
+        let y = area_y + (height * top).round() as i32;
+        let cropped_width = ((width * (1.0 - left - right)).round() as i32).max(1);
+        let cropped_height = ((height * (1.0 - top - bottom)).round() as i32).max(1);
+
+        Some((x, y, cropped_width, cropped_height))
+    }
+
+    /// Area of the decoded frame left once uniform black bars are removed, or `None` if there
+    /// are none. Only frames with an 8-bit luma plane are analyzed
+    fn detect_active_area(&self, threshold: u8) -> Option<(i32, i32, i32, i32)> {
+        let format = unsafe { (*self.frame).format };
+        let has_luma_plane = [
+            AVPixelFormat::AV_PIX_FMT_YUV420P,
+            AVPixelFormat::AV_PIX_FMT_YUVJ420P,
+            AVPixelFormat::AV_PIX_FMT_YUV422P,
+            AVPixelFormat::AV_PIX_FMT_YUVJ422P,
+            AVPixelFormat::AV_PIX_FMT_YUV444P,
+            AVPixelFormat::AV_PIX_FMT_YUVJ444P,
+            AVPixelFormat::AV_PIX_FMT_YUV440P,
+            AVPixelFormat::AV_PIX_FMT_YUVJ440P,
+            AVPixelFormat::AV_PIX_FMT_YUV411P,
+            AVPixelFormat::AV_PIX_FMT_YUV410P,
+            AVPixelFormat::AV_PIX_FMT_NV12,
+            AVPixelFormat::AV_PIX_FMT_NV21,
+            AVPixelFormat::AV_PIX_FMT_GRAY8,
+        ]
+        .iter()
+        .any(|pixel_format| *pixel_format as c_int == format);
+
+        let (width, height, stride) = unsafe {
+            (
+                (*self.frame).width,
+                (*self.frame).height,
+                (*self.frame).linesize[0],
+            )
+        };
+        if !has_luma_plane || width <= 0 || height <= 0 || stride < width {
+            return None;
+        }
+
+        let luma = unsafe {
+            std::slice::from_raw_parts(
+                (*self.frame).data[0],
+                stride as usize * (height as usize - 1) + width as usize,
+            )
+        };
+        let area = find_active_area(
+            luma,
+            stride as usize,
+            width as usize,
+            height as usize,
+            threshold,
+        );
+        if area == (0, 0, width, height) {
+            None
         } else {
-            Some(FrameSource::VideoStream)
+            Some(area)
+        }
+    }
+
+    /// Dimensions of the decoded frame once cropped, as handed to the scale filter
+    fn get_cropped_dimensions(&self) -> (i32, i32) {
+        match self.get_crop_rectangle() {
+            Some((_, _, width, height)) => (width, height),
+            None => unsafe {
+                (
+                    (*self.video_codec_context).width,
+                    (*self.video_codec_context).height,
+                )
+            },
+        }
+    }
+
+    fn get_codec_name(&self) -> String {
+        unsafe { CStr::from_ptr(avcodec_get_name((*self.video_codec_context).codec_id)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn get_pixel_format_name(&self) -> String {
+        let name = unsafe { av_get_pix_fmt_name((*self.video_codec_context).pix_fmt) };
+        if name.is_null() {
+            return "unknown".to_string();
+        }
+        unsafe { CStr::from_ptr(name) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Duration of the input, in whole seconds
+    pub fn get_video_duration(&self) -> Duration {
+        if let Some(duration) = self.options.assume_duration {
+            return Duration::from_secs(duration.as_secs());
+        }
+        let duration = unsafe { (*self.format_context).duration };
+        // Images, and streams of unknown length, have no duration
+        if duration == AV_NOPTS_VALUE || duration < 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs(duration as u64 / AV_TIME_BASE as u64)
+    }
+
+    /// Position of the last decoded frame in the video stream, when it has a timestamp
+    pub fn get_frame_timestamp(&self) -> Option<Duration> {
+        let (timestamp, start_time, time_base) = unsafe {
+            (
+                (*self.frame).best_effort_timestamp,
+                (*self.video_stream).start_time,
+                (*self.video_stream).time_base,
+            )
         };
+        if timestamp == AV_NOPTS_VALUE {
+            return None;
+        }
 
-        let frame_data_size = video_frame.line_size as usize * video_frame.height as usize;
-        match video_frame.data.capacity() {
-            0 => {
-                video_frame.data = Vec::with_capacity(frame_data_size);
-            }
-            c if c < frame_data_size => {
-                video_frame.data.reserve_exact(frame_data_size - c);
-                video_frame.data.clear();
-            }
-            c if c > frame_data_size => {
-                video_frame.data.shrink_to(frame_data_size);
-                video_frame.data.clear();
-            }
-            _ => {
-                video_frame.data.clear();
+        let start_time = if start_time == AV_NOPTS_VALUE {
+            0
+        } else {
+            start_time
+        };
+        let seconds = (timestamp - start_time).max(0) as f64 * time_base.num as f64
+            / time_base.den.max(1) as f64;
+        Some(Duration::from_secs_f64(seconds))
+    }
+
+    pub(crate) fn get_decode_stats(&self) -> DecodeStats {
+        DecodeStats {
+            wall_time: self.opened_at.elapsed(),
+            ..self.decode_stats
+        }
+    }
+
+    /// Number of frames of the video stream, when the container records it
+    pub(crate) fn get_frame_count(&self) -> Option<u64> {
+        let frame_count = unsafe { (*self.video_stream).nb_frames };
+        (frame_count > 0).then_some(frame_count as u64)
+    }
+
+    /// Number of packets of the video stream, found by reading them all from the start without
+    /// decoding them, for inputs such as animated images whose container doesn't record it
+    pub(crate) fn count_frames(&mut self) -> Result<u64, ThumbnailerError> {
+        if !self.allow_seek {
+            return Err(ThumbnailerError::SeekNotAllowed);
+        }
+
+        check_error(
+            unsafe {
+                av_seek_frame(
+                    self.format_context,
+                    self.video_stream_index,
+                    i64::MIN,
+                    AVSEEK_FLAG_BACKWARD,
+                )
+            },
+            "Seeking video failed",
+        )?;
+        self.decode_stats.seeks += 1;
+
+        let mut count = 0;
+        while self.get_video_packet() {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Position of the `frame_index`-th frame of the video stream, found by reading packets from
+    /// the start without decoding them, or `None` if the stream ends before. Packets being in
+    /// decoding order, the position may be a few frames off when frames are reordered
+    pub(crate) fn get_frame_position(
+        &mut self,
+        frame_index: u64,
+    ) -> Result<Option<Duration>, ThumbnailerError> {
+        if !self.allow_seek {
+            return Err(ThumbnailerError::SeekNotAllowed);
+        }
+
+        check_error(
+            unsafe {
+                av_seek_frame(
+                    self.format_context,
+                    self.video_stream_index,
+                    i64::MIN,
+                    AVSEEK_FLAG_BACKWARD,
+                )
+            },
+            "Seeking video failed",
+        )?;
+        self.decode_stats.seeks += 1;
+
+        let mut index = 0;
+        while self.get_video_packet() {
+            if index == frame_index {
+                let (timestamp, start_time, time_base) = unsafe {
+                    let timestamp = match (*self.packet).pts {
+                        AV_NOPTS_VALUE => (*self.packet).dts,
+                        pts => pts,
+                    };
+                    (
+                        timestamp,
+                        (*self.video_stream).start_time,
+                        (*self.video_stream).time_base,
+                    )
+                };
+                if timestamp == AV_NOPTS_VALUE {
+                    return Ok(None);
+                }
+
+                let start_time = if start_time == AV_NOPTS_VALUE {
+                    0
+                } else {
+                    start_time
+                };
+                let seconds = (timestamp - start_time).max(0) as f64 * time_base.num as f64
+                    / time_base.den.max(1) as f64;
+                return Ok(Some(Duration::from_secs_f64(seconds)));
             }
+            index += 1;
         }
+        Ok(None)
+    }
 
-        video_frame.data.extend_from_slice(unsafe {
-            std::slice::from_raw_parts((*new_frame.as_mut_ptr()).data[0], frame_data_size)
-        });
+    /// Whether the input was opened by one of FFmpeg's image demuxers, the image being then
+    /// handled as a video made of a single frame
+    fn input_is_still_image(&self) -> bool {
+        let input_format = unsafe { (*self.format_context).iformat };
+        if input_format.is_null() {
+            return false;
+        }
 
-        unsafe { avfilter_graph_free(&mut self.filter_graph) };
+        let name = unsafe { CStr::from_ptr((*input_format).name) }.to_string_lossy();
+        name == "image2" || name.ends_with("_pipe")
+    }
 
-        Ok(())
+    /// Whether a video stream of the opened input has its dimensions and pixel format known
+    /// without probing
+    fn header_describes_video(&self) -> bool {
+        let streams = unsafe {
+            std::slice::from_raw_parts(
+                (*self.format_context).streams,
+                (*self.format_context).nb_streams as usize,
+            )
+        };
+        streams.iter().any(|&stream| {
+            let parameters = unsafe { &*(*stream).codecpar };
+            parameters.codec_type == AVMediaType::AVMEDIA_TYPE_VIDEO
+                && parameters.width > 0
+                && parameters.height > 0
+                && parameters.format >= 0
+        })
     }
 
-    pub(crate) fn get_video_duration(&self) -> Duration {
-        Duration::from_secs(unsafe { (*self.format_context).duration as u64 / AV_TIME_BASE as u64 })
+    /// Whether the input is an animated GIF or PNG, which the demuxers of still images leave to
+    /// these dedicated ones
+    fn input_is_animated_image(&self) -> bool {
+        let input_format = unsafe { (*self.format_context).iformat };
+        if input_format.is_null() {
+            return false;
+        }
+
+        let name = unsafe { CStr::from_ptr((*input_format).name) }.to_string_lossy();
+        name == "gif" || name == "apng"
+    }
+
+    pub(crate) fn is_animated_image(&self) -> bool {
+        self.is_animated_image
     }
 
-    fn initialize_video(&mut self, prefer_embedded_metadata: bool) -> Result<(), ThumbnailerError> {
-        self.find_preferred_video_stream(prefer_embedded_metadata)?;
+    fn initialize_video(&mut self) -> Result<(), ThumbnailerError> {
+        self.find_preferred_video_stream(self.options.prefer_embedded_metadata)?;
 
         self.video_stream = unsafe {
             *(*self.format_context)
                 .streams
                 .offset(self.video_stream_index as isize)
         };
+
+        if let Some(max_source_dimension) = self.options.max_source_dimension {
+            let (width, height) = unsafe {
+                (
+                    (*(*self.video_stream).codecpar).width.max(0) as u32,
+                    (*(*self.video_stream).codecpar).height.max(0) as u32,
+                )
+            };
+            if width > max_source_dimension || height > max_source_dimension {
+                return Err(ThumbnailerError::SourceTooLarge {
+                    width,
+                    height,
+                    max_source_dimension,
+                });
+            }
+        }
         self.video_codec =
             unsafe { avcodec_find_decoder((*(*self.video_stream).codecpar).codec_id) };
         if self.video_codec.is_null() {
@@ -356,7 +1213,20 @@ impl MovieDecoder {
         self.use_embedded_data = false;
         if prefer_embedded_metadata && !embedded_data_streams.is_empty() {
             self.use_embedded_data = true;
-            self.video_stream_index = embedded_data_streams[0];
+            self.video_stream_index = self
+                .options
+                .preferred_art_type
+                .and_then(|art_type| {
+                    embedded_data_streams
+                        .iter()
+                        .copied()
+                        .find(|&stream_idx| unsafe {
+                            let stream =
+                                *(*self.format_context).streams.offset(stream_idx as isize);
+                            attachments::stream_art_type(stream) == Some(art_type)
+                        })
+                })
+                .unwrap_or(embedded_data_streams[0]);
             Ok(())
         } else if !video_streams.is_empty() {
             self.video_stream_index = video_streams[0];
@@ -366,6 +1236,26 @@ impl MovieDecoder {
         }
     }
 
+    /// Index of the subtitle stream to burn, relative to the other subtitle streams as expected by
+    /// the `subtitles` filter, preferring the one flagged as default
+    fn find_subtitle_stream(&self) -> Option<i32> {
+        let mut subtitle_streams = vec![];
+
+        for stream_idx in 0..(unsafe { (*self.format_context).nb_streams as isize }) {
+            let stream = unsafe { *(*self.format_context).streams.offset(stream_idx) };
+            if unsafe { (*(*stream).codecpar).codec_type } == AVMediaType::AVMEDIA_TYPE_SUBTITLE {
+                subtitle_streams
+                    .push(unsafe { (*stream).disposition } & AV_DISPOSITION_DEFAULT != 0);
+            }
+        }
+
+        subtitle_streams
+            .iter()
+            .position(|is_default| *is_default)
+            .or_else(|| (!subtitle_streams.is_empty()).then_some(0))
+            .map(|index| index as i32)
+    }
+
     fn get_video_packet(&mut self) -> bool {
         let mut frames_available = true;
         let mut frame_decoded = false;
@@ -382,17 +1272,20 @@ impl MovieDecoder {
         while frames_available && !frame_decoded {
             frames_available = unsafe { av_read_frame(self.format_context, self.packet) == 0 };
             if frames_available {
+                self.decode_stats.packets_read += 1;
                 frame_decoded = unsafe { (*self.packet).stream_index } == self.video_stream_index;
                 if !frame_decoded {
                     unsafe { av_packet_unref(self.packet) };
                 }
+            } else {
+                self.end_of_input = true;
             }
         }
 
         frame_decoded
     }
 
-    fn decode_video_packet(&self) -> Result<bool, ThumbnailerError> {
+    fn decode_video_packet(&mut self) -> Result<bool, ThumbnailerError> {
         if unsafe { (*self.packet).stream_index } != self.video_stream_index {
             return Ok(false);
         }
@@ -410,7 +1303,10 @@ impl MovieDecoder {
         }
 
         match unsafe { avcodec_receive_frame(self.video_codec_context, self.frame) } {
-            0 => Ok(true),
+            0 => {
+                self.decode_stats.frames_decoded += 1;
+                Ok(true)
+            }
             AVERROR_EAGAIN => Ok(false),
             e => Err(ThumbnailerError::FfmpegWithReason(
                 FfmpegError::from(e),
@@ -424,6 +1320,7 @@ impl MovieDecoder {
         timebase: &AVRational,
         scaled_size: Option<ThumbnailSize>,
         maintain_aspect_ratio: bool,
+        pixel_format: &str,
     ) -> Result<(), ThumbnailerError> {
         unsafe { self.filter_graph = avfilter_graph_alloc() };
         if self.filter_graph.is_null() {
@@ -460,8 +1357,11 @@ impl MovieDecoder {
             "Failed to create filter sink",
         )?;
 
-        let mut yadif_filter = std::ptr::null_mut();
+        // Filters are linked in the order they are pushed, from the source to the sink
+        let mut filter_chain = vec![(self.filter_source, "source")];
+
         if unsafe { (*self.frame).interlaced_frame } != 0 {
+            let mut yadif_filter = std::ptr::null_mut();
             setup_filter(
                 &mut yadif_filter,
                 "yadif",
@@ -470,30 +1370,85 @@ impl MovieDecoder {
                 self.filter_graph,
                 "Failed to create deinterlace filter",
             )?;
+            filter_chain.push((yadif_filter, "yadif"));
+        }
+
+        if self.options.burn_subtitles && !self.use_embedded_data {
+            if let Some(subtitle_stream) = self.subtitle_stream {
+                let mut subtitles_filter = std::ptr::null_mut();
+                setup_filter(
+                    &mut subtitles_filter,
+                    "subtitles",
+                    "thumb_subtitles",
+                    &format!(
+                        "filename='{}':si={subtitle_stream}",
+                        escape_filter_value(&self.input_file.to_string_lossy())
+                    ),
+                    self.filter_graph,
+                    "Failed to create subtitles filter",
+                )?;
+                filter_chain.push((subtitles_filter, "subtitles"));
+            }
+        }
+
+        if let Some((x, y, width, height)) = self.get_crop_rectangle() {
+            let mut crop_filter = std::ptr::null_mut();
+            setup_filter(
+                &mut crop_filter,
+                "crop",
+                "thumb_crop",
+                &format!("w={width}:h={height}:x={x}:y={y}"),
+                self.filter_graph,
+                "Failed to create crop filter",
+            )?;
+            filter_chain.push((crop_filter, "crop"));
         }
 
+        if let Some(strength) = self.options.denoise_strength {
+            let mut denoise_filter = std::ptr::null_mut();
+            setup_filter(
+                &mut denoise_filter,
+                "hqdn3d",
+                "thumb_denoise",
+                &format!("luma_spatial={strength}"),
+                self.filter_graph,
+                "Failed to create denoise filter",
+            )?;
+            filter_chain.push((denoise_filter, "denoise"));
+        }
+
+        let mut scale_args = self.create_scale_string(scaled_size, maintain_aspect_ratio)?;
+        if self.options.dither {
+            // The format filter follows directly, so the conversion happens in this scaler
+            scale_args += ":flags=bicubic+accurate_rnd+full_chroma_int:sws_dither=ed";
+        }
         let mut scale_filter = std::ptr::null_mut();
         setup_filter(
             &mut scale_filter,
             "scale",
             "thumb_scale",
-            &self.create_scale_string(scaled_size, maintain_aspect_ratio)?,
+            &scale_args,
             self.filter_graph,
             "Failed to create scale filter",
         )?;
+        filter_chain.push((scale_filter, "scale"));
 
         let mut format_filter = std::ptr::null_mut();
         setup_filter(
             &mut format_filter,
             "format",
             "thumb_format",
-            "pix_fmts=rgb24",
+            &format!("pix_fmts={pixel_format}"),
             self.filter_graph,
             "Failed to create format filter",
         )?;
+        filter_chain.push((format_filter, "format"));
 
         let mut rotate_filter = std::ptr::null_mut();
-        let rotation = self.get_stream_rotation();
+        let rotation = match self.options.rotation_handling {
+            RotationHandling::Physical => self.get_stream_rotation(),
+            RotationHandling::ExifTag => -1,
+        };
         if rotation == 3 {
             setup_filter(
                 &mut rotate_filter,
@@ -503,6 +1458,7 @@ impl MovieDecoder {
                 self.filter_graph,
                 "Failed to create rotate filter",
             )?;
+            filter_chain.push((rotate_filter, "rotate"));
         } else if rotation != -1 {
             setup_filter(
                 &mut rotate_filter,
@@ -512,59 +1468,20 @@ impl MovieDecoder {
                 self.filter_graph,
                 "Failed to create transpose filter",
             )?;
+            filter_chain.push((rotate_filter, "transpose"));
         }
 
-        check_error(
-            unsafe {
-                avfilter_link(
-                    if !rotate_filter.is_null() {
-                        rotate_filter
-                    } else {
-                        format_filter
-                    },
-                    0,
-                    self.filter_sink,
-                    0,
-                )
-            },
-            "Failed to link final filter",
-        )?;
+        filter_chain.push((self.filter_sink, "sink"));
 
-        if !rotate_filter.is_null() {
+        for link in filter_chain.windows(2) {
+            let (source, source_name) = link[0];
+            let (destination, _) = link[1];
             check_error(
-                unsafe { avfilter_link(format_filter, 0, rotate_filter, 0) },
-                "Failed to link format filter",
+                unsafe { avfilter_link(source, 0, destination, 0) },
+                &format!("Failed to link {source_name} filter"),
             )?;
         }
 
-        check_error(
-            unsafe { avfilter_link(scale_filter, 0, format_filter, 0) },
-            "Failed to link scale filter",
-        )?;
-
-        if !yadif_filter.is_null() {
-            check_error(
-                unsafe { avfilter_link(yadif_filter, 0, scale_filter, 0) },
-                "Failed to link yadif filter",
-            )?;
-        }
-
-        check_error(
-            unsafe {
-                avfilter_link(
-                    self.filter_source,
-                    0,
-                    if !yadif_filter.is_null() {
-                        yadif_filter
-                    } else {
-                        scale_filter
-                    },
-                    0,
-                )
-            },
-            "Failed to link source filter",
-        )?;
-
         check_error(
             unsafe { avfilter_graph_config(self.filter_graph, std::ptr::null_mut()) },
             "Failed to configure filter graph",
@@ -594,14 +1511,59 @@ impl MovieDecoder {
             ThumbnailSize::Size(width) => {
                 scaled_width = width as i32;
             }
+            ThumbnailSize::Height(height) => {
+                let transposed = matches!(self.get_stream_rotation(), 1 | 2);
+                let aspect_ratio = unsafe {
+                    av_guess_sample_aspect_ratio(self.format_context, self.video_stream, self.frame)
+                };
+                let aspect_ratio = if aspect_ratio.num == 0 {
+                    (1, 1)
+                } else {
+                    (aspect_ratio.num, aspect_ratio.den)
+                };
+                let (cropped_width, cropped_height) = self.get_cropped_dimensions();
+                // An unbounded box width leaves the height as the only constraint
+                let (fitted_width, fitted_height) = fit_to_box(
+                    cropped_width,
+                    cropped_height,
+                    aspect_ratio,
+                    transposed,
+                    (i32::MAX, height as i32),
+                );
+                return Ok(format!("w={fitted_width}:h={fitted_height}"));
+            }
         }
 
         let mut scale = String::new();
 
         if scaled_width != -1 && scaled_height != -1 {
-            let _ = write!(scale, "w={scaled_width}:h={scaled_height}");
-            if maintain_aspect_ratio {
-                let _ = write!(scale, ":force_original_aspect_ratio=decrease");
+            let transposed = matches!(self.get_stream_rotation(), 1 | 2);
+            let aspect_ratio = unsafe {
+                av_guess_sample_aspect_ratio(self.format_context, self.video_stream, self.frame)
+            };
+            let anamorphic = aspect_ratio.num != 0 && aspect_ratio.num != aspect_ratio.den;
+
+            if maintain_aspect_ratio && anamorphic {
+                // The scale filter ignores the sample aspect ratio when keeping the original
+                // aspect ratio, so we compute the final dimensions ourselves
+                let (cropped_width, cropped_height) = self.get_cropped_dimensions();
+                let (fitted_width, fitted_height) = fit_to_box(
+                    cropped_width,
+                    cropped_height,
+                    (aspect_ratio.num, aspect_ratio.den),
+                    transposed,
+                    (scaled_width, scaled_height),
+                );
+                let _ = write!(scale, "w={fitted_width}:h={fitted_height}");
+            } else {
+                // Requested dimensions are the displayed ones, but scaling happens before rotation
+                if transposed {
+                    std::mem::swap(&mut scaled_width, &mut scaled_height);
+                }
+                let _ = write!(scale, "w={scaled_width}:h={scaled_height}");
+                if maintain_aspect_ratio {
+                    let _ = write!(scale, ":force_original_aspect_ratio=decrease");
+                }
             }
         } else if !maintain_aspect_ratio {
             if scaled_width == -1 {
@@ -618,10 +1580,8 @@ impl MovieDecoder {
 
             let anamorphic;
             let aspect_ratio;
+            (scaled_width, scaled_height) = self.get_cropped_dimensions();
             unsafe {
-                scaled_width = (*self.video_codec_context).width;
-                scaled_height = (*self.video_codec_context).height;
-
                 aspect_ratio = av_guess_sample_aspect_ratio(
                     self.format_context,
                     self.video_stream,
@@ -670,7 +1630,103 @@ impl MovieDecoder {
         Ok(scale)
     }
 
+    /// ICC profile attached to the decoded frame, as done by image decoders, or else to the video
+    /// stream
+    fn get_icc_profile(&self) -> Option<Vec<u8>> {
+        let side_data = unsafe {
+            av_frame_get_side_data(self.frame, AVFrameSideDataType::AV_FRAME_DATA_ICC_PROFILE)
+        };
+        if !side_data.is_null() {
+            let (data, size) = unsafe { ((*side_data).data, (*side_data).size) };
+            if !data.is_null() && size > 0 {
+                return Some(unsafe { std::slice::from_raw_parts(data, size) }.to_vec());
+            }
+        }
+
+        let mut size = 0;
+        let data = unsafe {
+            av_stream_get_side_data(
+                self.video_stream,
+                AVPacketSideDataType::AV_PKT_DATA_ICC_PROFILE,
+                &mut size,
+            )
+        };
+        if data.is_null() || size == 0 {
+            return None;
+        }
+        Some(unsafe { std::slice::from_raw_parts(data, size) }.to_vec())
+    }
+
+    /// Rotation to apply to display the video stream upright: -1 for none, 1 and 2 for the
+    /// `transpose` filter's clockwise and counterclockwise quarter turns, 3 for a half turn
     fn get_stream_rotation(&self) -> i32 {
+        self.rotation
+    }
+
+    /// Reads the rotation from the display matrix of the video stream, or from its `rotate`
+    /// metadata tag when it has none, as some MOV files only set one of the two. Angles that
+    /// aren't a multiple of 90°, as written by some stabilizing phones, are rounded to the nearest
+    /// one with a warning, rather than rotating by an arbitrary angle
+    fn read_stream_rotation(&self) -> i32 {
+        // Counterclockwise angle, the display matrix taking precedence
+        let angle = match self
+            .display_matrix_angle()
+            .or_else(|| self.rotate_tag_angle())
+        {
+            Some(angle) => angle,
+            None => return -1,
+        };
+        self.rotation_from_angle(angle)
+    }
+
+    /// Updates the rotation from the display matrix attached to the decoded frame, which
+    /// segmented recordings carry on their packets whenever the orientation changes mid-file, so
+    /// that each frame is rotated as it was recorded rather than as the first segment was
+    fn update_frame_rotation(&mut self) {
+        let side_data = unsafe {
+            av_frame_get_side_data(self.frame, AVFrameSideDataType::AV_FRAME_DATA_DISPLAYMATRIX)
+        };
+        if side_data.is_null() || unsafe { (*side_data).size } < 9 * std::mem::size_of::<i32>() {
+            return;
+        }
+
+        // NaN for an invalid matrix
+        let angle = unsafe { av_display_rotation_get((*side_data).data as *const i32) };
+        if !angle.is_nan() {
+            self.rotation = self.rotation_from_angle(angle);
+        }
+    }
+
+    /// Converts a counterclockwise angle to a rotation as returned by `get_stream_rotation`
+    fn rotation_from_angle(&self, angle: f64) -> i32 {
+        let quarter_turns = (angle / 90.0).round();
+        if (angle - quarter_turns * 90.0).abs() > 0.5 {
+            let message = CString::new(format!(
+                "Rounding the video rotation of {angle:.1}° to {:.0}°\n",
+                quarter_turns * 90.0
+            ))
+            .unwrap();
+            unsafe {
+                av_log(
+                    self.format_context as *mut c_void,
+                    AV_LOG_WARNING,
+                    b"%s\0".as_ptr() as *const c_char,
+                    message.as_ptr(),
+                )
+            };
+        }
+
+        match (quarter_turns as i32).rem_euclid(4) {
+            1 => 2,
+            2 => 3,
+            3 => 1,
+            _ => -1,
+        }
+    }
+
+    /// Counterclockwise angle in range [-180.0, 180.0] of the display matrix of the video stream,
+    /// if it has a valid one
+    fn display_matrix_angle(&self) -> Option<f64> {
         let matrix = unsafe {
             av_stream_get_side_data(
                 self.video_stream,
@@ -678,22 +1734,58 @@ impl MovieDecoder {
                 std::ptr::null_mut(),
             )
         } as *const i32;
+        if matrix.is_null() {
+            return None;
+        }
 
-        if !matrix.is_null() {
-            let angle = (unsafe { av_display_rotation_get(matrix) }).round();
-            if angle < -135.0 {
-                return 3;
-            } else if angle > 45.0 && angle < 135.0 {
-                return 2;
-            } else if angle < -45.0 && angle > -135.0 {
-                return 1;
-            }
+        // NaN for an invalid matrix
+        let angle = unsafe { av_display_rotation_get(matrix) };
+        (!angle.is_nan()).then_some(angle)
+    }
+
+    /// Counterclockwise angle of the `rotate` metadata tag of the video stream, which holds a
+    /// clockwise angle in degrees
+    fn rotate_tag_angle(&self) -> Option<f64> {
+        let tag = unsafe {
+            av_dict_get(
+                (*self.video_stream).metadata,
+                b"rotate\0".as_ptr() as *const c_char,
+                std::ptr::null(),
+                0,
+            )
+        };
+        if tag.is_null() {
+            return None;
         }
 
-        -1
+        let value = unsafe { CStr::from_ptr((*tag).value) }.to_string_lossy();
+        let angle = value.trim().parse::<f64>().ok()?;
+        angle.is_finite().then_some(-angle)
     }
 }
 
+/// Name of the format FFmpeg associates with the extension of `input_file`, if any
+fn guess_format(input_file: &CStr) -> Option<String> {
+    let format =
+        unsafe { av_guess_format(std::ptr::null(), input_file.as_ptr(), std::ptr::null()) };
+    if format.is_null() {
+        return None;
+    }
+    let name = unsafe { CStr::from_ptr((*format).name) }.to_string_lossy();
+    Some(name.into_owned())
+}
+
+// SAFETY: every FFmpeg context is exclusively owned by the decoder and never handed out, so moving
+// it to another thread is sound as long as only one thread uses it at a time, which `&mut self`
+// methods already guarantee
+unsafe impl Send for MovieDecoder {}
+
+/// Interrupt callback of FFmpeg, aborting blocking operations once the deadline pointed to by
+/// `opaque` is past
+unsafe extern "C" fn interrupt_after_deadline(opaque: *mut c_void) -> c_int {
+    (Instant::now() >= *(opaque as *const Instant)) as c_int
+}
+
 impl Drop for MovieDecoder {
     fn drop(&mut self) {
         if !self.video_codec_context.is_null() {
@@ -729,6 +1821,93 @@ impl Drop for MovieDecoder {
     }
 }
 
+/// Bounding box `(x, y, width, height)` of the rows and columns of the `luma` plane whose mean
+/// is above `threshold`, the whole plane if all of them are black
+fn find_active_area(
+    luma: &[u8],
+    stride: usize,
+    width: usize,
+    height: usize,
+    threshold: u8,
+) -> (i32, i32, i32, i32) {
+    let is_black = |sum: u64, count: usize| sum <= threshold as u64 * count as u64;
+    let row_is_black = |y: usize| {
+        let row = &luma[y * stride..y * stride + width];
+        is_black(row.iter().map(|&value| value as u64).sum(), width)
+    };
+
+    let top = match (0..height).find(|&y| !row_is_black(y)) {
+        Some(top) => top,
+        None => return (0, 0, width as i32, height as i32),
+    };
+    let bottom = (top..height)
+        .rev()
+        .find(|&y| !row_is_black(y))
+        .unwrap_or(top);
+
+    let column_is_black = |x: usize| {
+        let sum = (top..=bottom).map(|y| luma[y * stride + x] as u64).sum();
+        is_black(sum, bottom - top + 1)
+    };
+    let left = (0..width).find(|&x| !column_is_black(x)).unwrap_or(0);
+    let right = (left..width)
+        .rev()
+        .find(|&x| !column_is_black(x))
+        .unwrap_or(width - 1);
+
+    (
+        left as i32,
+        top as i32,
+        (right - left + 1) as i32,
+        (bottom - top + 1) as i32,
+    )
+}
+
+/// Fits a `width`x`height` frame in a `box_width`x`box_height` box, in the same order as the
+/// frame is displayed: the sample aspect ratio is applied first, then the rotation, `transposed`
+/// being true for 90° rotations. Returned dimensions are in the frame's orientation before
+/// rotation, as expected by the scale filter
+fn fit_to_box(
+    width: i32,
+    height: i32,
+    sample_aspect_ratio: (i32, i32),
+    transposed: bool,
+    (box_width, box_height): (i32, i32),
+) -> (i32, i32) {
+    let (num, den) = sample_aspect_ratio;
+    let corrected_width = i64::from(width) * i64::from(num) / i64::from(den.max(1));
+    let corrected_height = i64::from(height);
+
+    let (display_width, display_height) = if transposed {
+        (corrected_height, corrected_width)
+    } else {
+        (corrected_width, corrected_height)
+    };
+
+    let (box_width, box_height) = (i64::from(box_width), i64::from(box_height));
+    let (fitted_width, fitted_height) = if box_width * display_height <= box_height * display_width
+    {
+        (box_width, display_height * box_width / display_width.max(1))
+    } else {
+        (
+            display_width * box_height / display_height.max(1),
+            box_height,
+        )
+    };
+    let (fitted_width, fitted_height) = (fitted_width.max(1) as i32, fitted_height.max(1) as i32);
+
+    if transposed {
+        (fitted_height, fitted_width)
+    } else {
+        (fitted_width, fitted_height)
+    }
+}
+
+/// Escapes a value so it can be put between single quotes in a filter arguments string
+fn escape_filter_value(value: &str) -> String {
+    value.replace('\'', "'\\''")
+}
+
 fn check_error(return_code: i32, error_message: &str) -> Result<(), ThumbnailerError> {
     if return_code < 0 {
         Err(ThumbnailerError::FfmpegWithReason(
@@ -791,3 +1970,60 @@ fn setup_filter_without_args(
         error_message,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_to_box_anamorphic() {
+        // 720x480 with a 32:27 sample aspect ratio is displayed as 853x480
+        assert_eq!(
+            fit_to_box(720, 480, (32, 27), false, (400, 400)),
+            (400, 225)
+        );
+        assert_eq!(
+            fit_to_box(720, 480, (32, 27), false, (400, 100)),
+            (177, 100)
+        );
+    }
+
+    #[test]
+    fn fit_to_box_rotated_anamorphic() {
+        // Rotated by 90°, the displayed frame is 480x853, fitting as 225x400 in a 400x400 box,
+        // which is 400x225 before the rotation is applied
+        assert_eq!(fit_to_box(720, 480, (32, 27), true, (400, 400)), (400, 225));
+        // Displayed as 200x355 in a 200x400 box
+        assert_eq!(fit_to_box(720, 480, (32, 27), true, (200, 400)), (355, 200));
+    }
+
+    #[test]
+    fn fit_to_box_rotated_square_pixels() {
+        assert_eq!(fit_to_box(1920, 1080, (1, 1), true, (100, 400)), (177, 100));
+    }
+
+    #[test]
+    fn find_active_area_letterbox() {
+        // 8x6 frame whose two top and bottom rows are black bars, with a gray picture between
+        let mut luma = vec![16; 8 * 6];
+        luma[2 * 8..4 * 8].fill(128);
+        assert_eq!(find_active_area(&luma, 8, 8, 6, 24), (0, 2, 8, 2));
+    }
+
+    #[test]
+    fn find_active_area_pillarbox_with_padded_stride() {
+        // 6x4 frame stored with a stride of 8, its first and last columns being black bars
+        let mut luma = vec![255; 8 * 4];
+        for row in luma.chunks_mut(8) {
+            row[0] = 0;
+            row[5] = 0;
+        }
+        assert_eq!(find_active_area(&luma, 8, 6, 4, 24), (1, 0, 4, 4));
+    }
+
+    #[test]
+    fn find_active_area_black_frame() {
+        let luma = vec![0; 4 * 4];
+        assert_eq!(find_active_area(&luma, 4, 4, 4, 24), (0, 0, 4, 4));
+    }
+}
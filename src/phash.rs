@@ -0,0 +1,173 @@
+use crate::VideoFrame;
+
+use std::f64::consts::PI;
+
+/// Side of the luminance grid the frame is reduced to before the DCT
+const GRID_SIZE: usize = 32;
+/// Side of the block of lowest frequencies making the hash
+const HASH_SIZE: usize = 8;
+
+/// Perceptual hash and average hash of the RGB24 pixels of `video_frame`, each computed only if
+/// requested, from a single pass over its pixels
+pub(crate) fn hashes(
+    video_frame: &VideoFrame,
+    perceptual: bool,
+    average: bool,
+) -> (Option<u64>, Option<u64>) {
+    if !perceptual && !average {
+        return (None, None);
+    }
+    if video_frame.width == 0 || video_frame.height == 0 {
+        return (perceptual.then_some(0), average.then_some(0));
+    }
+
+    let grid = luminance_grid(video_frame);
+    (
+        perceptual.then(|| hash_of_grid(&grid)),
+        average.then(|| average_hash_of_grid(&grid)),
+    )
+}
+
+/// 64-bit DCT-based perceptual hash of `grid`: similar pictures, even at different sizes or
+/// qualities, have hashes differing by few bits
+fn hash_of_grid(grid: &[f64]) -> u64 {
+    let coefficients = low_frequencies(grid);
+    // The DC coefficient only tells the mean luminance, which would dominate the median
+    let mut sorted = coefficients[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+
+    coefficients
+        .iter()
+        .enumerate()
+        .filter(|(_, &coefficient)| coefficient > median)
+        .fold(0, |hash, (index, _)| hash | 1 << index)
+}
+
+/// 64-bit average hash of `grid`, reduced to `HASH_SIZE` x `HASH_SIZE` cells, each bit telling
+/// whether a cell is brighter than the mean. Cheaper and less robust than the perceptual hash,
+/// e.g. as a first-pass filter before comparing perceptual hashes
+fn average_hash_of_grid(grid: &[f64]) -> u64 {
+    const BLOCK_SIZE: usize = GRID_SIZE / HASH_SIZE;
+
+    let mut cells = [0.0; HASH_SIZE * HASH_SIZE];
+    for (index, &luminance) in grid.iter().enumerate() {
+        let (y, x) = (index / GRID_SIZE, index % GRID_SIZE);
+        cells[y / BLOCK_SIZE * HASH_SIZE + x / BLOCK_SIZE] += luminance;
+    }
+    let mean = cells.iter().sum::<f64>() / cells.len() as f64;
+
+    cells
+        .iter()
+        .enumerate()
+        .filter(|(_, &cell)| cell > mean)
+        .fold(0, |hash, (index, _)| hash | 1 << index)
+}
+
+/// Mean BT.601 luminance of the pixels covered by each cell of a `GRID_SIZE` sided grid
+fn luminance_grid(video_frame: &VideoFrame) -> Vec<f64> {
+    let (width, height) = (video_frame.width as usize, video_frame.height as usize);
+    let mut sums = vec![0.0; GRID_SIZE * GRID_SIZE];
+    let mut counts = vec![0u32; GRID_SIZE * GRID_SIZE];
+
+    for (y, row) in video_frame
+        .data
+        .chunks(video_frame.line_size as usize)
+        .take(height)
+        .enumerate()
+    {
+        let cell_y = y * GRID_SIZE / height;
+        for (x, pixel) in row[..(width * 3).min(row.len())]
+            .chunks_exact(3)
+            .enumerate()
+        {
+            let cell = cell_y * GRID_SIZE + x * GRID_SIZE / width;
+            sums[cell] +=
+                0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64;
+            counts[cell] += 1;
+        }
+    }
+
+    // Frames smaller than the grid leave some cells empty, filled with the previous one
+    let mut grid = vec![0.0; GRID_SIZE * GRID_SIZE];
+    for cell in 0..grid.len() {
+        grid[cell] = match counts[cell] {
+            0 if cell > 0 => grid[cell - 1],
+            0 => 0.0,
+            count => sums[cell] / count as f64,
+        };
+    }
+    grid
+}
+
+/// `HASH_SIZE` x `HASH_SIZE` lowest frequency coefficients of the 2D DCT-II of `grid`, row major
+fn low_frequencies(grid: &[f64]) -> Vec<f64> {
+    let basis = |frequency: usize, position: usize| {
+        ((2 * position + 1) as f64 * frequency as f64 * PI / (2 * GRID_SIZE) as f64).cos()
+    };
+
+    // Separable transform, the rows first then the columns
+    let mut rows = vec![0.0; GRID_SIZE * HASH_SIZE];
+    for y in 0..GRID_SIZE {
+        for u in 0..HASH_SIZE {
+            rows[y * HASH_SIZE + u] = (0..GRID_SIZE)
+                .map(|x| grid[y * GRID_SIZE + x] * basis(u, x))
+                .sum();
+        }
+    }
+
+    let mut coefficients = vec![0.0; HASH_SIZE * HASH_SIZE];
+    for v in 0..HASH_SIZE {
+        for u in 0..HASH_SIZE {
+            coefficients[v * HASH_SIZE + u] = (0..GRID_SIZE)
+                .map(|y| rows[y * HASH_SIZE + u] * basis(v, y))
+                .sum();
+        }
+    }
+    coefficients
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gray frame with a bright square in its top left quarter
+    fn frame(size: u32) -> VideoFrame {
+        let mut data = vec![64; (size * size * 3) as usize];
+        for y in 0..size / 2 {
+            let start = (y * size * 3) as usize;
+            data[start..start + (size / 2 * 3) as usize].fill(224);
+        }
+        VideoFrame {
+            width: size,
+            height: size,
+            line_size: size * 3,
+            data,
+            ..Default::default()
+        }
+    }
+
+    fn perceptual_hash(video_frame: &VideoFrame) -> u64 {
+        hashes(video_frame, true, false).0.unwrap()
+    }
+
+    #[test]
+    fn hash_ignores_scale() {
+        assert_eq!(perceptual_hash(&frame(64)), perceptual_hash(&frame(128)));
+    }
+
+    #[test]
+    fn hash_tells_pictures_apart() {
+        let mut flipped = frame(64);
+        flipped.data.reverse();
+        assert_ne!(perceptual_hash(&frame(64)), perceptual_hash(&flipped));
+    }
+
+    #[test]
+    fn average_hash_marks_bright_quarter() {
+        let (_, average) = hashes(&frame(64), false, true);
+        // Top left 4x4 cells of the 8x8 hash
+        let expected = (0..4).fold(0, |hash, row| hash | 0x0f << (row * 8));
+        assert_eq!(average, Some(expected));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{
+    film_strip::FilmStripConfig,
+    film_strip_filter,
+    video_frame::{Orientation, SourceMetadata},
+    OutputContainer, VideoFrame,
+};
+
+use std::{thread, time::Duration};
+
+/// Grid of frames sampled evenly across a video, obtained from
+/// `Thumbnailer::process_to_sprite_sheet`. Cells are laid out left to right then top to bottom,
+/// in the order of `timestamps`.
+#[derive(Debug)]
+pub struct SpriteSheet {
+    pub columns: u32,
+    pub rows: u32,
+    pub cell_width: u32,
+    pub cell_height: u32,
+    /// Position in the video of the frame in each cell
+    pub timestamps: Vec<Duration>,
+    /// Presentation timestamp of the frame actually captured in each cell, which seeking may
+    /// place a little off its position in `timestamps`, e.g. for the cues of a WebVTT thumbnail
+    /// track. Falls back to the position for frames without a timestamp
+    pub frame_timestamps: Vec<Duration>,
+    pub image: OutputContainer,
+}
+
+/// Layout of a sprite sheet written by `Thumbnailer::process_sprite_sheet_to_writer`, as in
+/// `SpriteSheet` whose image went to the writer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpriteSheetLayout {
+    pub columns: u32,
+    pub rows: u32,
+    pub cell_width: u32,
+    pub cell_height: u32,
+    /// Position in the video of the frame in each cell
+    pub timestamps: Vec<Duration>,
+    /// Presentation timestamp of the frame actually captured in each cell, falling back to the
+    /// position for frames without a timestamp
+    pub frame_timestamps: Vec<Duration>,
+}
+
+/// Composites `cells`, all scaled to the dimensions of the first one, into a `columns` wide
+/// grid. The film strip and the copy of each grid row happen on up to `threads` threads, every
+/// one writing its own band of rows so that the layout never depends on which one completes first
+pub(crate) fn composite(
+    cells: &mut [VideoFrame],
+    columns: u32,
+    film_strip: Option<FilmStripConfig>,
+    threads: usize,
+) -> VideoFrame {
+    let cell_size = (cells[0].width as usize, cells[0].height as usize);
+    composite_with_cell_size(cells, columns, cell_size, film_strip, threads)
+}
+
+/// Composites `cells` as `composite` does, cells being cropped or padded to `cell_size`, e.g. for
+/// every grid row of a sheet composited one at a time to match the first one
+pub(crate) fn composite_with_cell_size(
+    cells: &mut [VideoFrame],
+    columns: u32,
+    (cell_width, cell_height): (usize, usize),
+    film_strip: Option<FilmStripConfig>,
+    threads: usize,
+) -> VideoFrame {
+    let columns = columns as usize;
+    let rows = (cells.len() + columns - 1) / columns;
+
+    let line_size = cell_width * columns * 3;
+    let band_size = line_size * cell_height;
+    let mut data = vec![0; band_size * rows];
+
+    let threads = threads.clamp(1, rows);
+    let rows_per_thread = (rows + threads - 1) / threads;
+
+    thread::scope(|scope| {
+        for (bands, cells) in data
+            .chunks_mut(band_size * rows_per_thread)
+            .zip(cells.chunks_mut(columns * rows_per_thread))
+        {
+            scope.spawn(move || {
+                for (band, cells) in bands.chunks_mut(band_size).zip(cells.chunks_mut(columns)) {
+                    for (column, cell) in cells.iter_mut().enumerate() {
+                        if let Some(film_strip) = film_strip {
+                            film_strip_filter(cell, film_strip);
+                        }
+                        copy_cell(cell, band, line_size, column, (cell_width, cell_height));
+                    }
+                }
+            });
+        }
+    });
+
+    let width = (cell_width * columns) as u32;
+    let height = (cell_height * rows) as u32;
+    VideoFrame {
+        width,
+        height,
+        line_size: line_size as u32,
+        data,
+        source_width: cells[0].source_width,
+        source_height: cells[0].source_height,
+        coded_dimensions: cells[0].coded_dimensions,
+        display_dimensions: cells[0].display_dimensions,
+        orientation: Some(Orientation::from_dimensions(width, height)),
+        was_upscaled: cells[0].was_upscaled,
+        source_codec: cells[0].source_codec.clone(),
+        source_pixel_format: cells[0].source_pixel_format.clone(),
+        icc_profile: cells[0].icc_profile.clone(),
+        // Cells come from many positions, so only the source is kept
+        source_metadata: cells[0]
+            .source_metadata
+            .clone()
+            .map(|source_metadata| SourceMetadata {
+                seek_time: None,
+                ..source_metadata
+            }),
+        // Counted since the input was opened, so the last cell accounts for all of them
+        decode_stats: cells[cells.len() - 1].decode_stats,
+        ..Default::default()
+    }
+}
+
+/// Copies `cell` into its `column` of `band`, cropping it to the cell dimensions should it differ
+fn copy_cell(
+    cell: &VideoFrame,
+    band: &mut [u8],
+    line_size: usize,
+    column: usize,
+    (cell_width, cell_height): (usize, usize),
+) {
+    let offset = column * cell_width * 3;
+    let row_length = cell.width.min(cell_width as u32) as usize * 3;
+    for (y, row) in cell
+        .data
+        .chunks(cell.line_size as usize)
+        .take(cell.height.min(cell_height as u32) as usize)
+        .enumerate()
+    {
+        let row_length = row_length.min(row.len());
+        let start = y * line_size + offset;
+        band[start..start + row_length].copy_from_slice(&row[..row_length]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(value: u8) -> VideoFrame {
+        VideoFrame {
+            width: 2,
+            height: 1,
+            line_size: 6,
+            data: vec![value; 6],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn composite_keeps_cell_order() {
+        let mut cells: Vec<_> = (1..=5).map(cell).collect();
+        let sheet = composite(&mut cells, 2, None, 2);
+
+        assert_eq!((sheet.width, sheet.height, sheet.line_size), (4, 3, 12));
+        let expected: Vec<u8> = [1, 2, 3, 4, 5, 0]
+            .iter()
+            .flat_map(|&value| [value; 6])
+            .collect();
+        assert_eq!(sheet.data, expected);
+    }
+}
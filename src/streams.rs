@@ -0,0 +1,96 @@
+use crate::{attachments::InputFile, error::FfmpegError, ThumbnailerError};
+
+use ffmpeg_sys_next::{
+    avcodec_get_name, avformat_find_stream_info, AVDurationEstimationMethod, AVMediaType,
+    AVRational, AV_NOPTS_VALUE, AV_TIME_BASE,
+};
+use std::{ffi::CStr, path::Path, time::Duration};
+
+/// Where the duration of a stream comes from, telling how far seeking to a percentage of it can
+/// be trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DurationSource {
+    /// The header of the container, for the whole file
+    Container,
+    /// The header of the stream itself
+    Stream,
+    /// Estimated by FFmpeg from the bitrate and the file size, which may be far off
+    Estimated,
+    /// Neither the container nor the stream tell it, as for live streams
+    Unknown,
+}
+
+/// Properties of a video stream of a container, obtained from `Thumbnailer::list_video_streams`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamInfo {
+    /// Index of the stream among all the streams of the container
+    pub index: usize,
+    pub width: u32,
+    pub height: u32,
+    /// FFmpeg name of the codec of the stream, e.g. `h264`
+    pub codec: String,
+    /// Average frame rate, when the container or the probed packets tell it
+    pub fps: Option<f64>,
+    /// Duration of the stream, or of the whole container when the stream doesn't tell it
+    pub duration: Option<Duration>,
+    pub duration_source: DurationSource,
+}
+
+pub(crate) fn list_video_streams(path: &Path) -> Result<Vec<StreamInfo>, ThumbnailerError> {
+    let input = InputFile::open(path)?;
+    // Some containers only describe their streams in the packets, which this probes
+    let result = unsafe { avformat_find_stream_info(input.format_context, std::ptr::null_mut()) };
+    if result < 0 {
+        return Err(ThumbnailerError::FfmpegWithReason(
+            FfmpegError::from(result),
+            "Failed to get stream info".to_string(),
+        ));
+    }
+
+    let (container_duration, estimation_method) = unsafe {
+        (
+            (*input.format_context).duration,
+            (*input.format_context).duration_estimation_method,
+        )
+    };
+    let container_duration = (container_duration != AV_NOPTS_VALUE && container_duration >= 0)
+        .then(|| container_duration as f64 / AV_TIME_BASE as f64);
+    // FFmpeg fills the durations of streams and container alike with its estimate
+    let estimated = estimation_method == AVDurationEstimationMethod::AVFMT_DURATION_FROM_BITRATE;
+
+    let streams = input
+        .streams_of_type(AVMediaType::AVMEDIA_TYPE_VIDEO)
+        .map(|stream| unsafe {
+            let codec_parameters = &*(*stream).codecpar;
+            let frame_rate = match (*stream).avg_frame_rate {
+                AVRational { num: 0, .. } | AVRational { den: 0, .. } => (*stream).r_frame_rate,
+                frame_rate => frame_rate,
+            };
+            let (duration, time_base) = ((*stream).duration, (*stream).time_base);
+            let duration = (duration != AV_NOPTS_VALUE && duration >= 0 && time_base.den > 0)
+                .then(|| duration as f64 * time_base.num as f64 / time_base.den as f64);
+            let (duration, duration_source) = match (duration, container_duration) {
+                (Some(duration), _) | (None, Some(duration)) if estimated => {
+                    (Some(duration), DurationSource::Estimated)
+                }
+                (Some(duration), _) => (Some(duration), DurationSource::Stream),
+                (None, Some(duration)) => (Some(duration), DurationSource::Container),
+                (None, None) => (None, DurationSource::Unknown),
+            };
+
+            StreamInfo {
+                index: (*stream).index as usize,
+                width: codec_parameters.width.max(0) as u32,
+                height: codec_parameters.height.max(0) as u32,
+                codec: CStr::from_ptr(avcodec_get_name(codec_parameters.codec_id))
+                    .to_string_lossy()
+                    .into_owned(),
+                fps: (frame_rate.num > 0 && frame_rate.den > 0)
+                    .then(|| frame_rate.num as f64 / frame_rate.den as f64),
+                duration: duration.map(Duration::from_secs_f64),
+                duration_source,
+            }
+        })
+        .collect();
+    Ok(streams)
+}
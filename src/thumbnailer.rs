@@ -1,10 +1,140 @@
 use crate::{
-    film_strip_filter, MovieDecoder, OutputContainer, OutputFormat, ThumbnailSize,
-    ThumbnailerError, VideoFrame,
+    attachments,
+    encoder::CustomEncoder,
+    film_strip::FilmStripConfig,
+    film_strip_filter,
+    filters::{self, ColorAdjustment, Corner, FrameAccumulator, PlayButton},
+    frame_selector::CustomFrameSelector,
+    logging, metadata,
+    movie_decoder::{Crop, DecoderOptions},
+    sprite_sheet, streams, ArtType, Attachment, BlankFrameDetection, ChannelOrder, EmbeddedPicture,
+    Encoder, FrameSelector, FrameSource, LogLevel, MovieDecoder, OutputContainer, OutputFormat,
+    QualityPreset, RotationHandling, RowOrder, SpriteSheet, StreamInfo, ThumbnailSize,
+    ThumbnailerError, VideoFrame, YuvFrame,
 };
 
-use std::{ops::Deref, path::Path};
-use tokio::task::spawn_blocking;
+#[cfg(feature = "face-detect")]
+use crate::face_detection;
+#[cfg(feature = "phash")]
+use crate::phash;
+#[cfg(feature = "png")]
+use crate::SpriteSheetLayout;
+#[cfg(feature = "png")]
+use std::borrow::Cow;
+#[cfg(any(feature = "jpeg", feature = "png"))]
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+#[cfg(feature = "fs")]
+use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    mem,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+use tokio::{runtime::Handle, task::JoinHandle};
+
+#[cfg(feature = "fs")]
+use crate::utils::{is_transient, write_atomically};
+
+/// Boundary delimiting the frames written by `Thumbnailer::process_to_mjpeg_writer`, to be
+/// announced with a `multipart/x-mixed-replace; boundary=ffmpegthumbnailer-frame` content type
+#[cfg(feature = "jpeg")]
+pub const MJPEG_BOUNDARY: &str = "ffmpegthumbnailer-frame";
+
+/// Encodings tried by `ThumbnailerBuilder::target_max_bytes` to lower the quality, enough to
+/// narrow the 100 quality points down to less than one
+const TARGET_SIZE_STEPS: u32 = 7;
+
+/// Delay before the first retry of `ThumbnailerBuilder::write_retries`, doubled on every retry
+#[cfg(feature = "fs")]
+const WRITE_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Number of frames compared by `FrameSelection::Brightest`
+const BRIGHTEST_SAMPLES: u32 = 8;
+
+/// Number of frames searched for a face by `FrameSelection::WithFace`
+#[cfg(feature = "face-detect")]
+const FACE_SAMPLES: u32 = 8;
+
+/// How the frame used as a thumbnail is chosen in the video stream. Embedded metadata, when used,
+/// always takes precedence
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FrameSelection {
+    /// The frame at the seek percentage, once the intro is skipped
+    #[default]
+    Seek,
+    /// The frame with the highest mean luminance among frames sampled evenly over `window`,
+    /// starting at the seek position, for consistently dark videos
+    Brightest { window: Duration },
+    /// The first key frame of the video stream, without seeking at all, which is the fastest and
+    /// avoids the gray frames found before the first key frame of some files
+    FirstKeyframe,
+    /// The mean of `count` frames spread evenly over the video, once the intro is skipped,
+    /// blended into a single summary picture
+    AverageOfN { count: usize },
+    /// The first frame with a detected face among frames sampled evenly over `window`, starting
+    /// at the seek position, falling back to the frame at the seek position when none has one.
+    /// Requires a model set with `ThumbnailerBuilder::face_detection_model`
+    #[cfg(feature = "face-detect")]
+    WithFace { window: Duration },
+}
+
+/// Input of `Thumbnailer::process_range`, a byte window of which is thumbnailed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeInput {
+    /// A file, only the bytes of the window being read
+    Path(PathBuf),
+    /// The whole input, already in memory
+    Bytes(Vec<u8>),
+}
+
+impl From<PathBuf> for RangeInput {
+    fn from(path: PathBuf) -> Self {
+        RangeInput::Path(path)
+    }
+}
+
+impl From<&Path> for RangeInput {
+    fn from(path: &Path) -> Self {
+        RangeInput::Path(path.to_path_buf())
+    }
+}
+
+impl From<Vec<u8>> for RangeInput {
+    fn from(bytes: Vec<u8>) -> Self {
+        RangeInput::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for RangeInput {
+    fn from(bytes: &[u8]) -> Self {
+        RangeInput::Bytes(bytes.to_vec())
+    }
+}
+
+impl RangeInput {
+    /// Bytes of the window `[start, end)`, cut short if the input ends before `end`
+    fn read_range(self, start: u64, end: u64) -> Result<Vec<u8>, ThumbnailerError> {
+        match self {
+            RangeInput::Path(path) => {
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(start))?;
+                let mut data = Vec::new();
+                file.take(end - start).read_to_end(&mut data)?;
+                Ok(data)
+            }
+            RangeInput::Bytes(bytes) => {
+                let length = bytes.len() as u64;
+                Ok(bytes[start.min(length) as usize..end.min(length) as usize].to_vec())
+            }
+        }
+    }
+}
 
 /// `Thumbnailer` struct holds data from a `ThumbnailerBuilder`, exposing methods
 /// to generate thumbnails from video files.
@@ -21,12 +151,127 @@ impl Thumbnailer {
         output_format: OutputFormat,
     ) -> Result<OutputContainer, ThumbnailerError> {
         let frame = self.process_to_video_frame(video_file_path).await?;
-        match output_format {
-            #[cfg(feature = "webp")]
-            OutputFormat::Webp => self.process_to_webp_bytes(frame).await,
-            #[cfg(feature = "png")]
-            OutputFormat::Png => self.process_to_png_bytes(frame).await,
+        let builder = self.builder.clone();
+        self.builder
+            .spawn_blocking(move || builder.encode(&frame, output_format))
+            .await?
+    }
+
+    /// Processes an video input file and returns the 64-bit perceptual hash of its thumbnail
+    /// frame, near-identical videos having hashes differing by few bits
+    #[cfg(feature = "phash")]
+    pub async fn process_to_phash(
+        &self,
+        video_file_path: impl AsRef<Path>,
+    ) -> Result<u64, ThumbnailerError> {
+        let thumbnailer = ThumbnailerBuilder {
+            with_phash: true,
+            ..self.builder.clone()
+        }
+        .build();
+        let frame = thumbnailer.process_to_video_frame(video_file_path).await?;
+        Ok(frame.phash.unwrap_or_default())
+    }
+
+    /// Processes an video input file and returns the 64-bit average hash of its thumbnail frame,
+    /// cheaper than the perceptual hash but less robust, e.g. to pre-filter duplicate candidates
+    #[cfg(feature = "phash")]
+    pub async fn process_to_ahash(
+        &self,
+        video_file_path: impl AsRef<Path>,
+    ) -> Result<u64, ThumbnailerError> {
+        let thumbnailer = ThumbnailerBuilder {
+            with_ahash: true,
+            ..self.builder.clone()
+        }
+        .build();
+        let frame = thumbnailer.process_to_video_frame(video_file_path).await?;
+        Ok(frame.ahash.unwrap_or_default())
+    }
+
+    /// Processes an video input file and returns the thumbnail frame along with its bytes encoded
+    /// in a specific format, e.g. to analyze the pixels without decoding the video a second time
+    pub async fn process_to_frame_and_bytes(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        output_format: OutputFormat,
+    ) -> Result<(VideoFrame, OutputContainer), ThumbnailerError> {
+        let frame = self.process_to_video_frame(video_file_path).await?;
+        let builder = self.builder.clone();
+        self.builder
+            .spawn_blocking(move || {
+                let container = builder.encode(&frame, output_format)?;
+                Ok((frame, container))
+            })
+            .await?
+    }
+
+    /// Processes an video input file and outputs bytes for the first format of `output_formats`
+    /// that encodes successfully, along with the format that was used. The video is decoded only
+    /// once, whatever the number of formats tried.
+    pub async fn process_to_first_supported(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        output_formats: &[OutputFormat],
+    ) -> Result<(OutputFormat, OutputContainer), ThumbnailerError> {
+        if output_formats.is_empty() {
+            return Err(ThumbnailerError::NoSupportedFormat);
         }
+
+        let frame = self.process_to_video_frame(video_file_path).await?;
+        let builder = self.builder.clone();
+        let output_formats = output_formats.to_vec();
+        self.builder
+            .spawn_blocking(move || {
+                let mut last_error = ThumbnailerError::NoSupportedFormat;
+                for output_format in output_formats {
+                    match builder.encode(&frame, output_format) {
+                        Ok(container) => return Ok((output_format, container)),
+                        Err(e) => last_error = e,
+                    }
+                }
+                Err(last_error)
+            })
+            .await?
+    }
+
+    /// Processes an video input file and encodes the thumbnail to each format of
+    /// `output_formats`. The video is decoded and scaled only once, failing as a whole if that
+    /// fails, while each format gets its own encoding result.
+    pub async fn process_to_multiple(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        output_formats: &[OutputFormat],
+    ) -> Result<HashMap<OutputFormat, Result<OutputContainer, ThumbnailerError>>, ThumbnailerError>
+    {
+        let frame = self.process_to_video_frame(video_file_path).await?;
+        let builder = self.builder.clone();
+        let output_formats = output_formats.to_vec();
+        self.builder
+            .spawn_blocking(move || {
+                let mut results = HashMap::with_capacity(output_formats.len());
+                // Formats are encoded in batches of at most `parallelism` at once
+                for output_formats in output_formats.chunks(builder.thread_count()) {
+                    thread::scope(|scope| {
+                        let encodings: Vec<_> = output_formats
+                            .iter()
+                            .map(|&output_format| {
+                                let (builder, frame) = (&builder, &frame);
+                                scope.spawn(move || {
+                                    (output_format, builder.encode(frame, output_format))
+                                })
+                            })
+                            .collect();
+                        for encoding in encodings {
+                            let (output_format, result) = encoding.join().unwrap();
+                            results.insert(output_format, result);
+                        }
+                    });
+                }
+                results
+            })
+            .await
+            .map_err(Into::into)
     }
 
     /// Processes an video input file and write to file system a thumbnail with webp format
@@ -36,22 +281,94 @@ impl Thumbnailer {
         video_file_path: impl AsRef<Path>,
         output_thumbnail_path: impl AsRef<Path>,
     ) -> Result<(), ThumbnailerError> {
-        let format = match output_thumbnail_path.as_ref().extension() {
-            #[cfg(feature = "webp")]
-            Some(ext) if ext.eq_ignore_ascii_case("webp") => OutputFormat::Webp,
-            #[cfg(feature = "png")]
-            Some(ext) if ext.eq_ignore_ascii_case("png") => OutputFormat::Png,
-            Some(ext) => return Err(ThumbnailerError::UnsupportedExtension(ext.to_owned())),
-            None => {
-                return Err(ThumbnailerError::UnsupportedExtension(
-                    "<empty>".to_owned().into(),
-                ))
-            }
-        };
+        let format = output_format_of(output_thumbnail_path.as_ref())?;
         let bytes = self.process_to_bytes(video_file_path, format).await?.bytes;
-        tokio::fs::write(output_thumbnail_path, bytes)
+        self.write_output(output_thumbnail_path.as_ref(), bytes)
             .await
-            .map_err(Into::into)
+    }
+
+    /// Processes an video input file and writes a thumbnail to each of `output_thumbnail_paths`,
+    /// in the format of its extension as for `process`. The video is decoded and scaled only
+    /// once, failing as a whole if that fails, while each path gets its own result.
+    #[cfg(feature = "fs")]
+    pub async fn process_multi(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        output_thumbnail_paths: &[impl AsRef<Path>],
+    ) -> Result<HashMap<PathBuf, Result<(), ThumbnailerError>>, ThumbnailerError> {
+        let frame = Arc::new(self.process_to_video_frame(video_file_path).await?);
+
+        let mut results = HashMap::with_capacity(output_thumbnail_paths.len());
+        for output_thumbnail_path in output_thumbnail_paths {
+            let output_thumbnail_path = output_thumbnail_path.as_ref().to_path_buf();
+            let result = self
+                .encode_and_write(Arc::clone(&frame), &output_thumbnail_path)
+                .await;
+            results.insert(output_thumbnail_path, result);
+        }
+        Ok(results)
+    }
+
+    #[cfg(feature = "fs")]
+    async fn encode_and_write(
+        &self,
+        frame: Arc<VideoFrame>,
+        output_thumbnail_path: &Path,
+    ) -> Result<(), ThumbnailerError> {
+        let format = output_format_of(output_thumbnail_path)?;
+        let builder = self.builder.clone();
+        let bytes = self
+            .builder
+            .spawn_blocking(move || builder.encode(&frame, format))
+            .await??
+            .bytes;
+        self.write_output(output_thumbnail_path, bytes).await
+    }
+
+    /// Writes `bytes` to `output_thumbnail_path`, atomically and retrying after transient errors
+    /// as set on the builder
+    #[cfg(feature = "fs")]
+    async fn write_output(
+        &self,
+        output_thumbnail_path: &Path,
+        bytes: Vec<u8>,
+    ) -> Result<(), ThumbnailerError> {
+        let mut delay = WRITE_RETRY_DELAY;
+        for _ in 0..self.builder.write_retries {
+            match self
+                .write_output_once(output_thumbnail_path, bytes.clone())
+                .await
+            {
+                Err(ThumbnailerError::Io(error)) if is_transient(&error) => {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+        self.write_output_once(output_thumbnail_path, bytes).await
+    }
+
+    #[cfg(feature = "fs")]
+    async fn write_output_once(
+        &self,
+        output_thumbnail_path: &Path,
+        bytes: Vec<u8>,
+    ) -> Result<(), ThumbnailerError> {
+        if self.builder.atomic_write {
+            let output_thumbnail_path = output_thumbnail_path.to_path_buf();
+            let temp_dir = self.builder.temp_dir.clone();
+            self.builder
+                .spawn_blocking(move || {
+                    write_atomically(&output_thumbnail_path, &bytes, temp_dir.as_deref())
+                })
+                .await??;
+            Ok(())
+        } else {
+            tokio::fs::write(output_thumbnail_path, bytes)
+                .await
+                .map_err(Into::into)
+        }
     }
 
     /// Processes an video input file and returns a webp encoded thumbnail as bytes
@@ -60,166 +377,2237 @@ impl Thumbnailer {
         video_file_path: impl AsRef<Path>,
     ) -> Result<VideoFrame, ThumbnailerError> {
         let video_file_path = video_file_path.as_ref().to_path_buf();
-        let prefer_embedded_metadata = self.builder.prefer_embedded_metadata;
-        let seek_percentage = self.builder.seek_percentage;
-        let size = self.builder.size;
-        let maintain_aspect_ratio = self.builder.maintain_aspect_ratio;
-        let with_film_strip = self.builder.with_film_strip;
+        let builder = self.builder.clone();
 
-        spawn_blocking(move || -> Result<VideoFrame, ThumbnailerError> {
-            let mut decoder = MovieDecoder::new(video_file_path, prefer_embedded_metadata)?;
-            // We actually have to decode a frame to get some metadata before we can start decoding for real
-            decoder.decode_video_frame()?;
+        self.builder
+            .spawn_blocking(move || -> Result<VideoFrame, ThumbnailerError> {
+                let mut decoder = open_decoder(video_file_path, &builder)?;
 
-            if !decoder.embedded_metadata_is_available() {
-                decoder.seek(
-                    (decoder.get_video_duration().as_secs() as f32 * seek_percentage).round()
-                        as i64,
+                let mut video_frame = VideoFrame::default();
+                builder.select_video_frame(&mut decoder, &mut video_frame)?;
+
+                Ok(video_frame)
+            })
+            .await?
+    }
+
+    /// Processes an video input file and returns the thumbnail as an image of the `image` crate,
+    /// for further transforms or to encode it to any format the crate supports
+    #[cfg(feature = "image")]
+    pub async fn process_to_dynamic_image(
+        &self,
+        video_file_path: impl AsRef<Path>,
+    ) -> Result<image::DynamicImage, ThumbnailerError> {
+        let video_frame = self.process_to_video_frame(video_file_path).await?;
+        Ok(video_frame.to_dynamic_image())
+    }
+
+    /// Decodes and scales the first frame of the video stream of an video input file, without
+    /// seeking nor applying the frame selection, hashes, color adjustments and decorations, as
+    /// the fastest check that a file is a decodable video. Its streams aren't probed either when
+    /// the container header describes them
+    pub async fn first_frame_only(
+        &self,
+        video_file_path: impl AsRef<Path>,
+    ) -> Result<VideoFrame, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        let builder = self.builder.clone();
+
+        self.builder
+            .spawn_blocking(move || -> Result<VideoFrame, ThumbnailerError> {
+                // The duration is never used, so assuming one only skips probing the streams
+                let decoder_options = DecoderOptions {
+                    prefer_embedded_metadata: false,
+                    burn_subtitles: false,
+                    assume_duration: Some(Duration::ZERO),
+                    ..builder.decoder_options()
+                };
+                let mut decoder = MovieDecoder::new(video_file_path, decoder_options)?;
+                decoder.decode_video_frame()?;
+
+                let mut video_frame = VideoFrame::default();
+                decoder.get_scaled_video_frame(
+                    Some(builder.size),
+                    builder.maintain_aspect_ratio,
+                    &mut video_frame,
                 )?;
-            }
+                video_frame.decode_stats = decoder.get_decode_stats();
 
-            let mut video_frame = VideoFrame::default();
+                Ok(video_frame)
+            })
+            .await?
+    }
 
-            decoder.get_scaled_video_frame(Some(size), maintain_aspect_ratio, &mut video_frame)?;
+    /// Decodes and scales the frame at the seek position of an video input file, skipping the
+    /// frame selection heuristics, hashes, color adjustments and decorations, to measure the cost
+    /// of decoding with the `decode_stats` of the returned frame independently of encoders
+    pub async fn decode_only(
+        &self,
+        video_file_path: impl AsRef<Path>,
+    ) -> Result<VideoFrame, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        let builder = self.builder.clone();
 
-            if with_film_strip {
-                film_strip_filter(&mut video_frame);
-            }
+        self.builder
+            .spawn_blocking(move || -> Result<VideoFrame, ThumbnailerError> {
+                let mut decoder = open_decoder(video_file_path, &builder)?;
 
-            Ok(video_frame)
-        })
-        .await?
+                let mut video_frame = VideoFrame::default();
+                decoder.get_scaled_video_frame(
+                    Some(builder.size),
+                    builder.maintain_aspect_ratio,
+                    &mut video_frame,
+                )?;
+                video_frame.decode_stats = decoder.get_decode_stats();
+
+                Ok(video_frame)
+            })
+            .await?
     }
 
-    #[cfg(feature = "webp")]
-    async fn process_to_webp_bytes(
+    /// Processes an already opened video file, read from the file descriptor `fd` through
+    /// FFmpeg's `fd:` protocol, and outputs bytes for a specific format, for sandboxes where
+    /// inputs can't be opened by path. FFmpeg reads from a duplicate of `fd`, which is left open,
+    /// but shares its file offset. `fd` must be seekable, e.g. a regular file rather than a pipe,
+    /// unless `FrameSelection::FirstKeyframe` is used. Subtitles can't be burned from it
+    #[cfg(unix)]
+    pub async fn process_fd(
         &self,
-        video_frame: VideoFrame,
+        fd: RawFd,
+        output_format: OutputFormat,
     ) -> Result<OutputContainer, ThumbnailerError> {
-        let quality = self.builder.quality;
-        // Type WebPMemory is !Send, which makes the Future in this function !Send,
-        // this make us `deref` to have a `&[u8]` and then `to_owned` to make a Vec<u8>
-        // which implies on a unwanted clone...{
-        spawn_blocking(move || {
-            let bytes =
-                webp::Encoder::from_rgb(&video_frame.data, video_frame.width, video_frame.height)
-                    .encode(quality)
-                    .deref()
-                    .to_vec();
-            Ok(OutputContainer::from(&video_frame, bytes))
-        })
-        .await?
+        let builder = self.builder.clone();
+
+        self.builder
+            .spawn_blocking(move || -> Result<OutputContainer, ThumbnailerError> {
+                let decoder_options = DecoderOptions {
+                    burn_subtitles: false,
+                    input_fd: Some(fd),
+                    ..builder.decoder_options()
+                };
+                let mut decoder =
+                    prepare_decoder(MovieDecoder::new("fd:", decoder_options)?, &builder)?;
+
+                let mut video_frame = VideoFrame::default();
+                builder.select_video_frame(&mut decoder, &mut video_frame)?;
+
+                builder.encode(&video_frame, output_format)
+            })
+            .await?
     }
 
-    #[cfg(feature = "png")]
-    async fn process_to_png_bytes(
+    /// Processes a video piped into the standard input, read through FFmpeg's `pipe:` protocol,
+    /// and outputs bytes for a specific format. As a pipe can't be seeked, this requires
+    /// `FrameSelection::FirstKeyframe`, failing with `ThumbnailerError::NonSeekableInput`
+    /// otherwise. Subtitles can't be burned from it
+    pub async fn process_stdin(
         &self,
-        video_frame: VideoFrame,
+        output_format: OutputFormat,
     ) -> Result<OutputContainer, ThumbnailerError> {
-        spawn_blocking(move || {
-            let buf: Vec<u8> = Vec::new();
-            let mut writer = std::io::BufWriter::new(buf);
-            let mut encoder = png::Encoder::new(&mut writer, video_frame.width, video_frame.height);
-            encoder.set_color(png::ColorType::Rgb);
-            encoder.set_depth(png::BitDepth::Eight);
-            encoder
-                .write_header()?
-                .write_image_data(&video_frame.data)?;
-            let bytes = writer.into_inner().unwrap();
-            Ok(OutputContainer::from(&video_frame, bytes))
-        })
-        .await?
+        self.process_pipe(0, output_format).await
     }
-}
 
-/// `ThumbnailerBuilder` struct holds data to build a `Thumbnailer` struct, exposing many methods
-/// to configure how a thumbnail must be generated.
-#[derive(Debug, Clone)]
-pub struct ThumbnailerBuilder {
-    maintain_aspect_ratio: bool,
-    size: ThumbnailSize,
-    seek_percentage: f32,
-    quality: f32,
-    prefer_embedded_metadata: bool,
-    with_film_strip: bool,
-}
+    /// Processes a video read from the pipe with file descriptor `fd`, as `process_stdin` does
+    async fn process_pipe(
+        &self,
+        fd: i32,
+        output_format: OutputFormat,
+    ) -> Result<OutputContainer, ThumbnailerError> {
+        if self.builder.frame_selection != FrameSelection::FirstKeyframe {
+            return Err(ThumbnailerError::NonSeekableInput);
+        }
+        let builder = self.builder.clone();
 
-impl Default for ThumbnailerBuilder {
-    fn default() -> Self {
-        Self {
-            maintain_aspect_ratio: true,
-            size: ThumbnailSize::Size(128),
-            seek_percentage: 0.1,
-            quality: 80.0,
-            prefer_embedded_metadata: true,
-            with_film_strip: true,
+        self.builder
+            .spawn_blocking(move || -> Result<OutputContainer, ThumbnailerError> {
+                let decoder_options = DecoderOptions {
+                    burn_subtitles: false,
+                    ..builder.decoder_options()
+                };
+                let mut decoder = prepare_decoder(
+                    MovieDecoder::new(format!("pipe:{fd}"), decoder_options)?,
+                    &builder,
+                )?;
+
+                let mut video_frame = VideoFrame::default();
+                builder.select_video_frame(&mut decoder, &mut video_frame)?;
+
+                builder.encode(&video_frame, output_format)
+            })
+            .await?
+    }
+
+    /// Processes a fragmented MP4 media segment, such as one of a DASH asset, along with the
+    /// initialization segment holding the codec setup it lacks, and outputs bytes for a specific
+    /// format. Both segments are read from memory, `init` first, so that FFmpeg sees a complete
+    /// fragmented file. Self-initializing media segments can be passed with an empty `init`
+    pub async fn process_fragmented(
+        &self,
+        init: &[u8],
+        media: &[u8],
+        output_format: OutputFormat,
+    ) -> Result<OutputContainer, ThumbnailerError> {
+        let mut data = Vec::with_capacity(init.len() + media.len());
+        data.extend_from_slice(init);
+        data.extend_from_slice(media);
+        let builder = self.builder.clone();
+
+        self.builder
+            .spawn_blocking(move || -> Result<OutputContainer, ThumbnailerError> {
+                let mut decoder = prepare_decoder(
+                    MovieDecoder::from_memory(data, builder.decoder_options())?,
+                    &builder,
+                )?;
+
+                let mut video_frame = VideoFrame::default();
+                builder.select_video_frame(&mut decoder, &mut video_frame)?;
+
+                builder.encode(&video_frame, output_format)
+            })
+            .await?
+    }
+
+    /// Processes only the bytes in range `[start, end)` of an input and outputs bytes for a
+    /// specific format, e.g. to thumbnail huge remote files from a range fetched around a key
+    /// frame. The window is read from memory, so it must be decodable on its own, as a range of
+    /// an MPEG-TS file or a self-initializing fragment is
+    pub async fn process_range(
+        &self,
+        input: impl Into<RangeInput>,
+        start: u64,
+        end: u64,
+        output_format: OutputFormat,
+    ) -> Result<OutputContainer, ThumbnailerError> {
+        if start >= end {
+            return Err(ThumbnailerError::InvalidByteRange { start, end });
         }
+        let input = input.into();
+        let builder = self.builder.clone();
+
+        self.builder
+            .spawn_blocking(move || -> Result<OutputContainer, ThumbnailerError> {
+                let data = input.read_range(start, end)?;
+                let mut video_frame = VideoFrame::default();
+                MovieDecoder::from_memory(data, builder.decoder_options())
+                    .map_err(ThumbnailerError::from)
+                    .and_then(|decoder| prepare_decoder(decoder, &builder))
+                    .and_then(|mut decoder| {
+                        builder.select_video_frame(&mut decoder, &mut video_frame)
+                    })
+                    .map_err(|error| match error {
+                        ThumbnailerError::Ffmpeg(_)
+                        | ThumbnailerError::FfmpegWithReason(..)
+                        | ThumbnailerError::FrameDecodeError
+                        | ThumbnailerError::SeekError
+                        | ThumbnailerError::TruncatedFile => {
+                            ThumbnailerError::NoFrameInByteRange { start, end }
+                        }
+                        error => error,
+                    })?;
+
+                builder.encode(&video_frame, output_format)
+            })
+            .await?
     }
-}
 
-impl ThumbnailerBuilder {
-    /// Creates a new `ThumbnailerBuilder` with default values:
-    /// - `maintain_aspect_ratio`: true
-    /// - `size`: 128 pixels
-    /// - `seek_percentage`: 10%
-    /// - `quality`: 80
-    /// - `prefer_embedded_metadata`: true
-    /// - `with_film_strip`: true
-    pub fn new() -> Self {
-        Default::default()
+    /// Opens a video input file, keeping it open to extract frames encoded with `output_format` at
+    /// any timestamp through the returned `FrameSource`. Frames always come from the video stream,
+    /// embedded metadata being ignored.
+    pub async fn open(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        output_format: OutputFormat,
+    ) -> Result<FrameSource, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        let builder = self.builder.clone();
+
+        let decoder = self
+            .builder
+            .spawn_blocking(move || open_video_stream_decoder(video_file_path, &builder))
+            .await??;
+
+        Ok(FrameSource::new(
+            self.builder.clone(),
+            output_format,
+            decoder,
+            (
+                self.builder.frame_cache_size,
+                self.builder.frame_cache_tolerance,
+            ),
+        ))
     }
 
-    /// To respect or not the aspect ratio from the video file in the generated thumbnail
-    pub fn maintain_aspect_ratio(mut self, maintain_aspect_ratio: bool) -> Self {
-        self.maintain_aspect_ratio = maintain_aspect_ratio;
-        self
+    /// Processes an video input file and outputs a thumbnail encoded with `output_format` for
+    /// each of `timestamps`, in order, e.g. as parsed by `parse_cue_sheet`. The video is opened
+    /// only once and seeked precisely to each timestamp, failing as a whole if it can't be opened,
+    /// while each timestamp gets its own result. Frames always come from the video stream,
+    /// embedded metadata being ignored
+    pub async fn process_from_cuesheet(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        timestamps: &[Duration],
+        output_format: OutputFormat,
+    ) -> Result<Vec<Result<OutputContainer, ThumbnailerError>>, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        let timestamps = timestamps.to_vec();
+        let builder = self.builder.clone();
+
+        self.builder
+            .spawn_blocking(move || {
+                let mut decoder = open_video_stream_decoder(video_file_path, &builder)?;
+                let mut video_frame = VideoFrame::default();
+                Ok(timestamps
+                    .into_iter()
+                    .map(|timestamp| {
+                        decoder.seek_exact(timestamp)?;
+                        builder.extract_video_frame(&mut decoder, &mut video_frame)?;
+                        builder.encode(&video_frame, output_format)
+                    })
+                    .collect())
+            })
+            .await?
     }
 
-    /// To set a thumbnail size, respecting or not its aspect ratio, according to `maintain_aspect_ratio` value
-    pub fn size(mut self, size: u32) -> Self {
-        self.size = ThumbnailSize::Size(size);
-        self
+    /// Opens a video input file to generate a thumbnail encoded with `output_format` for each of
+    /// its key frames, in stream order, through the returned `Keyframes`. Only key frames are
+    /// decoded, embedded metadata being ignored.
+    pub async fn process_all_keyframes(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        output_format: OutputFormat,
+    ) -> Result<Keyframes, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        let builder = self.builder.clone();
+
+        let decoder = self
+            .builder
+            .spawn_blocking(move || -> Result<MovieDecoder, ThumbnailerError> {
+                let decoder_options = DecoderOptions {
+                    prefer_embedded_metadata: false,
+                    ..builder.decoder_options()
+                };
+                let mut decoder = MovieDecoder::new(video_file_path, decoder_options)?;
+                decoder.skip_non_key_frames();
+                Ok(decoder)
+            })
+            .await??;
+
+        Ok(Keyframes::new(self.builder.clone(), output_format, decoder))
     }
 
-    /// To specify width and height of the thumbnail
-    pub fn width_and_height(mut self, width: u32, height: u32) -> Self {
-        self.size = ThumbnailSize::Dimensions { width, height };
-        self
+    /// Writes `frame_count` JPEG frames, sampled evenly across the video, into `writer` as a
+    /// multipart MJPEG stream whose parts are delimited by `MJPEG_BOUNDARY`. Each frame is written
+    /// as soon as it is encoded, and frames always come from the video stream, embedded metadata
+    /// being ignored. The writer is handed back once the stream is complete
+    #[cfg(feature = "jpeg")]
+    pub async fn process_to_mjpeg_writer<W: Write + Send + 'static>(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        frame_count: usize,
+        mut writer: W,
+    ) -> Result<W, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        let builder = self.builder.clone();
+
+        self.builder
+            .spawn_blocking(move || -> Result<W, ThumbnailerError> {
+                let mut decoder = open_video_stream_decoder(video_file_path, &builder)?;
+
+                let mut video_frame = VideoFrame::default();
+                for position in
+                    builder.interval_positions(decoder.get_video_duration(), frame_count)
+                {
+                    decoder.seek(position)?;
+                    builder.extract_video_frame(&mut decoder, &mut video_frame)?;
+                    let jpeg = builder.encode(&video_frame, OutputFormat::Jpeg)?.bytes;
+
+                    write!(
+                    writer,
+                    "--{MJPEG_BOUNDARY}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+                    jpeg.len()
+                )?;
+                    writer.write_all(&jpeg)?;
+                    writer.write_all(b"\r\n")?;
+                    writer.flush()?;
+                }
+                write!(writer, "--{MJPEG_BOUNDARY}--\r\n")?;
+                writer.flush()?;
+
+                Ok(writer)
+            })
+            .await?
     }
 
-    /// Seek percentage must be a value between 0.0 and 1.0
-    pub fn seek_percentage(mut self, seek_percentage: f32) -> Result<Self, ThumbnailerError> {
-        if !(0.0..=1.0).contains(&seek_percentage) {
-            return Err(ThumbnailerError::InvalidSeekPercentage(seek_percentage));
+    /// Processes an video input file into a sprite sheet of `columns` x `rows` frames sampled
+    /// evenly across the video, encoded with `output_format`. Frames always come from the video
+    /// stream, embedded metadata being ignored, and are always physically rotated
+    pub async fn process_to_sprite_sheet(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        columns: u32,
+        rows: u32,
+        output_format: OutputFormat,
+    ) -> Result<SpriteSheet, ThumbnailerError> {
+        if columns == 0 || rows == 0 {
+            return Err(ThumbnailerError::InvalidGrid { columns, rows });
         }
-        self.seek_percentage = seek_percentage;
-        Ok(self)
+
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        let builder = ThumbnailerBuilder {
+            rotation_handling: RotationHandling::Physical,
+            ..self.builder.clone()
+        };
+
+        self.builder
+            .spawn_blocking(move || {
+                builder.extract_sprite_sheet(video_file_path, columns, rows, output_format)
+            })
+            .await?
     }
 
-    /// Quality must be a value between 0.0 and 100.0
-    pub fn quality(mut self, quality: f32) -> Result<Self, ThumbnailerError> {
-        if !(0.0..=100.0).contains(&quality) {
-            return Err(ThumbnailerError::InvalidQuality(quality));
+    /// Processes an video input file into a PNG sprite sheet of `columns` x `rows` frames as
+    /// `process_to_sprite_sheet` does, streamed into `writer` one grid row at a time so that only
+    /// a grid row of frames is held in memory. The ICC profile and metadata of the source aren't
+    /// written. The writer is handed back along with the layout of the sheet once it is complete
+    #[cfg(feature = "png")]
+    pub async fn process_sprite_sheet_to_writer<W: Write + Send + 'static>(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        columns: u32,
+        rows: u32,
+        writer: W,
+    ) -> Result<(SpriteSheetLayout, W), ThumbnailerError> {
+        if columns == 0 || rows == 0 {
+            return Err(ThumbnailerError::InvalidGrid { columns, rows });
         }
-        self.quality = quality;
-        Ok(self)
-    }
 
-    /// To use embedded metadata in the video file, if available, instead of getting a frame as a
-    /// thumbnail
-    pub fn prefer_embedded_metadata(mut self, prefer_embedded_metadata: bool) -> Self {
-        self.prefer_embedded_metadata = prefer_embedded_metadata;
-        self
-    }
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        let builder = ThumbnailerBuilder {
+            rotation_handling: RotationHandling::Physical,
+            ..self.builder.clone()
+        };
 
-    /// If `with_film_strip` is true, a film strip will be added to the thumbnail borders
-    pub fn with_film_strip(mut self, with_film_strip: bool) -> Self {
-        self.with_film_strip = with_film_strip;
-        self
+        self.builder
+            .spawn_blocking(move || {
+                builder.stream_sprite_sheet(video_file_path, columns, rows, writer)
+            })
+            .await?
     }
 
-    /// Builds a `Thumbnailer` struct
-    pub fn build(self) -> Thumbnailer {
-        Thumbnailer { builder: self }
+    /// Processes an video input file and returns a single row of `frame_count` frames sampled
+    /// evenly across it, side by side. Every frame is scaled to the configured height, the
+    /// width following its aspect ratio.
+    pub async fn process_to_filmstrip(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        frame_count: u32,
+        output_format: OutputFormat,
+    ) -> Result<SpriteSheet, ThumbnailerError> {
+        if frame_count == 0 {
+            return Err(ThumbnailerError::InvalidGrid {
+                columns: frame_count,
+                rows: 1,
+            });
+        }
+
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        let height = match self.builder.size {
+            ThumbnailSize::Dimensions { height, .. } => height,
+            ThumbnailSize::Size(size) | ThumbnailSize::Height(size) => size,
+        };
+        let builder = ThumbnailerBuilder {
+            size: ThumbnailSize::Height(height),
+            rotation_handling: RotationHandling::Physical,
+            ..self.builder.clone()
+        };
+
+        self.builder
+            .spawn_blocking(move || {
+                builder.extract_sprite_sheet(video_file_path, frame_count, 1, output_format)
+            })
+            .await?
+    }
+
+    /// Lists the files attached to a container, such as the cover art of a Matroska file, which
+    /// aren't exposed as video streams
+    pub async fn list_attachments(
+        &self,
+        video_file_path: impl AsRef<Path>,
+    ) -> Result<Vec<Attachment>, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        self.builder
+            .spawn_blocking(move || attachments::list_attachments(&video_file_path))
+            .await?
+    }
+
+    /// Lists the video streams of a container with their properties, to inspect files holding
+    /// several of them
+    pub async fn list_video_streams(
+        &self,
+        video_file_path: impl AsRef<Path>,
+    ) -> Result<Vec<StreamInfo>, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        self.builder
+            .spawn_blocking(move || streams::list_video_streams(&video_file_path))
+            .await?
+    }
+
+    /// Returns the content of the attachment at stream `index`, as listed by `list_attachments`
+    pub async fn extract_attachment(
+        &self,
+        video_file_path: impl AsRef<Path>,
+        index: usize,
+    ) -> Result<Vec<u8>, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        self.builder
+            .spawn_blocking(move || attachments::extract_attachment(&video_file_path, index))
+            .await?
+    }
+
+    /// Returns the embedded picture of a video input file, such as its cover art, as originally
+    /// encoded along with its format, or `None` if it has none in a format enabled in this build.
+    /// Serving these bytes verbatim when the format matches the requested one avoids the quality
+    /// loss and the cost of re-encoding them, other files being processed as usual
+    pub async fn extract_embedded_raw(
+        &self,
+        video_file_path: impl AsRef<Path>,
+    ) -> Result<Option<(OutputFormat, Vec<u8>)>, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        self.builder
+            .spawn_blocking(move || attachments::extract_embedded_raw(&video_file_path))
+            .await?
+    }
+
+    /// Lists the pictures embedded in a video input file, such as its front and back covers, with
+    /// their kind as tagged, to choose the one used with `preferred_art_type`
+    pub async fn list_embedded_pictures(
+        &self,
+        video_file_path: impl AsRef<Path>,
+    ) -> Result<Vec<EmbeddedPicture>, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        self.builder
+            .spawn_blocking(move || attachments::list_embedded_pictures(&video_file_path))
+            .await?
+    }
+
+    /// Returns the width and height of the embedded picture of a video input file, such as its
+    /// cover art, or `None` if it has none, reading only its headers. Embedded pictures smaller
+    /// than the thumbnail size can then be skipped with `prefer_embedded_metadata` set to false
+    pub async fn embedded_thumbnail_dimensions(
+        &self,
+        video_file_path: impl AsRef<Path>,
+    ) -> Result<Option<(u32, u32)>, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        self.builder
+            .spawn_blocking(move || attachments::embedded_picture_dimensions(&video_file_path))
+            .await?
+    }
+
+    /// Processes an video input file and returns the raw planar YUV420 frame, skipping the
+    /// RGB conversion and the film strip
+    pub async fn process_to_yuv_frame(
+        &self,
+        video_file_path: impl AsRef<Path>,
+    ) -> Result<YuvFrame, ThumbnailerError> {
+        let video_file_path = video_file_path.as_ref().to_path_buf();
+        let builder = self.builder.clone();
+
+        self.builder
+            .spawn_blocking(move || -> Result<YuvFrame, ThumbnailerError> {
+                let mut decoder = open_decoder(video_file_path, &builder)?;
+
+                decoder.get_scaled_yuv_frame(Some(builder.size), builder.maintain_aspect_ratio)
+            })
+            .await?
+    }
+}
+
+#[cfg(feature = "webp")]
+fn encode_webp(
+    video_frame: &VideoFrame,
+    quality: f32,
+    near_lossless: Option<u8>,
+    dither: bool,
+) -> Result<OutputContainer, ThumbnailerError> {
+    use libwebp_sys::{
+        WebPConfig, WebPEncode, WebPEncodingError, WebPPicture, WebPPictureFree,
+        WebPPictureImportRGB, WebPValidateConfig,
+    };
+    use std::ffi::{c_int, c_void};
+
+    // Appends the encoded data to the `Vec<u8>` set as the picture's `custom_ptr`, so that the
+    // output is never copied out of a libwebp owned buffer
+    unsafe extern "C" fn write_to_vec(
+        data: *const u8,
+        data_size: usize,
+        picture: *const WebPPicture,
+    ) -> c_int {
+        let bytes = &mut *((*picture).custom_ptr as *mut Vec<u8>);
+        bytes.extend_from_slice(std::slice::from_raw_parts(data, data_size));
+        1
+    }
+
+    let invalid_configuration = || {
+        ThumbnailerError::WebpEncodingError(WebPEncodingError::VP8_ENC_ERROR_INVALID_CONFIGURATION)
+    };
+    let mut config = WebPConfig::new().map_err(|_| invalid_configuration())?;
+    // Near-lossless preprocessing only applies to lossless encoding, where the quality sets the
+    // compression effort
+    config.lossless = near_lossless.is_some() as c_int;
+    config.near_lossless = near_lossless.map_or(100, c_int::from);
+    config.alpha_compression = 1;
+    config.quality = quality;
+    // Pseudo-random dithering of the conversion of the RGB pixels to YUV
+    if dither {
+        config.preprocessing |= 2;
+    }
+    if unsafe { WebPValidateConfig(&config) } == 0 {
+        return Err(invalid_configuration());
+    }
+
+    // Degenerate inputs can lead to empty frames, which libwebp doesn't always reject
+    if video_frame.width == 0 || video_frame.height == 0 || video_frame.data.is_empty() {
+        return Err(ThumbnailerError::WebpEncodingError(
+            WebPEncodingError::VP8_ENC_ERROR_BAD_DIMENSION,
+        ));
+    }
+
+    let mut picture = WebPPicture::new().map_err(|_| invalid_configuration())?;
+    picture.use_argb = 1;
+    picture.width = video_frame.width as c_int;
+    picture.height = video_frame.height as c_int;
+
+    let mut bytes = Vec::new();
+    let status = unsafe {
+        let status = if WebPPictureImportRGB(
+            &mut picture,
+            video_frame.data.as_ptr(),
+            video_frame.line_size as c_int,
+        ) == 0
+        {
+            0
+        } else {
+            picture.writer = Some(write_to_vec);
+            picture.custom_ptr = &mut bytes as *mut Vec<u8> as *mut c_void;
+            WebPEncode(&config, &mut picture)
+        };
+        WebPPictureFree(&mut picture);
+        status
+    };
+    if status == 0 {
+        return Err(ThumbnailerError::WebpEncodingError(picture.error_code));
+    }
+    if bytes.is_empty() {
+        return Err(ThumbnailerError::WebpEncodingError(
+            WebPEncodingError::VP8_ENC_ERROR_BAD_WRITE,
+        ));
+    }
+
+    let exif = video_frame.exif_orientation.map(metadata::exif_orientation);
+    let xmp = video_frame
+        .source_metadata
+        .as_ref()
+        .map(metadata::xmp_packet);
+    let bytes = match (&video_frame.icc_profile, &exif, &xmp) {
+        (None, None, None) => bytes,
+        (icc_profile, exif, xmp) => metadata::webp_with_metadata(
+            &bytes,
+            video_frame.width,
+            video_frame.height,
+            icc_profile.as_deref(),
+            exif.as_deref(),
+            xmp.as_deref(),
+        ),
+    };
+    Ok(OutputContainer::from(
+        video_frame,
+        OutputFormat::Webp,
+        bytes,
+    ))
+}
+
+#[cfg(feature = "png")]
+fn encode_png(video_frame: &VideoFrame) -> Result<OutputContainer, ThumbnailerError> {
+    let buf: Vec<u8> = Vec::new();
+    let mut writer = std::io::BufWriter::new(buf);
+    let mut info = png::Info::with_size(video_frame.width, video_frame.height);
+    info.icc_profile = video_frame.icc_profile.as_deref().map(Cow::Borrowed);
+    let mut encoder = png::Encoder::with_info(&mut writer, info)?;
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    if let Some(source_metadata) = &video_frame.source_metadata {
+        for (keyword, text) in metadata::text_entries(source_metadata) {
+            // tEXt chunks are Latin-1 only, paths may need the UTF-8 of iTXt chunks
+            if text.is_ascii() {
+                encoder.add_text_chunk(keyword.to_string(), text)?;
+            } else {
+                encoder.add_itxt_chunk(keyword.to_string(), text)?;
+            }
+        }
+    }
+    let mut png_writer = encoder.write_header()?;
+    if let Some(orientation) = video_frame.exif_orientation {
+        png_writer.write_chunk(png::chunk::eXIf, &metadata::exif_orientation(orientation))?;
+    }
+    png_writer.write_image_data(&video_frame.data)?;
+    png_writer.finish()?;
+    let bytes = writer.into_inner().unwrap();
+    Ok(OutputContainer::from(video_frame, OutputFormat::Png, bytes))
+}
+
+#[cfg(feature = "jpeg")]
+fn encode_jpeg(
+    video_frame: &VideoFrame,
+    quality: f32,
+    progressive: bool,
+) -> Result<OutputContainer, ThumbnailerError> {
+    let too_large = || ThumbnailerError::JpegDimensionsTooLarge {
+        width: video_frame.width,
+        height: video_frame.height,
+    };
+    let width = u16::try_from(video_frame.width).map_err(|_| too_large())?;
+    let height = u16::try_from(video_frame.height).map_err(|_| too_large())?;
+
+    let mut bytes = Vec::new();
+    let mut encoder = jpeg_encoder::Encoder::new(&mut bytes, quality.clamp(1.0, 100.0) as u8);
+    encoder.set_progressive(progressive);
+    if let Some(icc_profile) = &video_frame.icc_profile {
+        encoder.add_icc_profile(icc_profile)?;
+    }
+    if let Some(orientation) = video_frame.exif_orientation {
+        let mut exif = b"Exif\0\0".to_vec();
+        exif.extend_from_slice(&metadata::exif_orientation(orientation));
+        encoder.add_app_segment(1, &exif)?;
+    }
+    if let Some(source_metadata) = &video_frame.source_metadata {
+        let mut xmp = metadata::JPEG_XMP_HEADER.to_vec();
+        xmp.extend_from_slice(&metadata::xmp_packet(source_metadata));
+        encoder.add_app_segment(1, &xmp)?;
+    }
+    encoder.encode(
+        &video_frame.data,
+        width,
+        height,
+        jpeg_encoder::ColorType::Rgb,
+    )?;
+    Ok(OutputContainer::from(
+        video_frame,
+        OutputFormat::Jpeg,
+        bytes,
+    ))
+}
+
+/// Copies the RGB24 pixels of `video_frame` in `channel_order` and `row_order`, leaving out the
+/// padding at the end of its rows
+fn pack_raw(
+    video_frame: &VideoFrame,
+    channel_order: ChannelOrder,
+    row_order: RowOrder,
+) -> OutputContainer {
+    let row_length = video_frame.width as usize * 3;
+    let mut bytes = Vec::with_capacity(
+        video_frame.width as usize * video_frame.height as usize * channel_order.channels(),
+    );
+    let rows = video_frame
+        .data
+        .chunks(video_frame.line_size as usize)
+        .take(video_frame.height as usize);
+    let rows: Box<dyn Iterator<Item = &[u8]>> = match row_order {
+        RowOrder::TopDown => Box::new(rows),
+        RowOrder::BottomUp => Box::new(rows.rev()),
+    };
+    for row in rows {
+        let row = &row[..row_length.min(row.len())];
+        match channel_order {
+            ChannelOrder::Rgb => bytes.extend_from_slice(row),
+            ChannelOrder::Bgr => {
+                for pixel in row.chunks_exact(3) {
+                    bytes.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+                }
+            }
+            ChannelOrder::Rgba => {
+                for pixel in row.chunks_exact(3) {
+                    bytes.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+                }
+            }
+            ChannelOrder::Bgra => {
+                for pixel in row.chunks_exact(3) {
+                    bytes.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 255]);
+                }
+            }
+        }
+    }
+    OutputContainer::from(video_frame, OutputFormat::Raw, bytes)
+}
+
+/// Output format matching the extension of `output_thumbnail_path`
+#[cfg(feature = "fs")]
+fn output_format_of(output_thumbnail_path: &Path) -> Result<OutputFormat, ThumbnailerError> {
+    match output_thumbnail_path.extension() {
+        #[cfg(feature = "webp")]
+        Some(ext) if ext.eq_ignore_ascii_case("webp") => Ok(OutputFormat::Webp),
+        #[cfg(feature = "png")]
+        Some(ext) if ext.eq_ignore_ascii_case("png") => Ok(OutputFormat::Png),
+        #[cfg(feature = "jpeg")]
+        Some(ext) if ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg") => {
+            Ok(OutputFormat::Jpeg)
+        }
+        Some(ext) if ext.eq_ignore_ascii_case("rgb") => Ok(OutputFormat::Raw),
+        Some(ext) => Err(ThumbnailerError::UnsupportedExtension(ext.to_owned())),
+        None => Err(ThumbnailerError::UnsupportedExtension(
+            "<empty>".to_owned().into(),
+        )),
+    }
+}
+
+/// Opens a decoder always reading frames from the video stream, ignoring embedded metadata
+fn open_video_stream_decoder(
+    video_file_path: PathBuf,
+    builder: &ThumbnailerBuilder,
+) -> Result<MovieDecoder, ThumbnailerError> {
+    let decoder_options = DecoderOptions {
+        prefer_embedded_metadata: false,
+        ..builder.decoder_options()
+    };
+
+    let mut decoder = MovieDecoder::new(video_file_path, decoder_options)?;
+    // We actually have to decode a frame to get some metadata before we can start decoding for real
+    decoder.decode_video_frame()?;
+    Ok(decoder)
+}
+
+fn open_decoder(
+    video_file_path: PathBuf,
+    builder: &ThumbnailerBuilder,
+) -> Result<MovieDecoder, ThumbnailerError> {
+    let decoder = MovieDecoder::new(&video_file_path, builder.decoder_options())?;
+    let uses_embedded_data = decoder.embedded_metadata_is_available();
+    match prepare_decoder(decoder, builder) {
+        // Corrupt embedded pictures are replaced by a frame of the video stream, the original
+        // error being kept for inputs without one, such as audio files with cover art
+        Err(error) if uses_embedded_data && !builder.strict_embedded => {
+            let decoder_options = DecoderOptions {
+                prefer_embedded_metadata: false,
+                ..builder.decoder_options()
+            };
+            MovieDecoder::new(video_file_path, decoder_options)
+                .and_then(|decoder| prepare_decoder(decoder, builder))
+                .map_err(|_| error)
+        }
+        result => result,
+    }
+}
+
+/// Decodes the first frame of a freshly opened decoder then moves it to the thumbnail position
+fn prepare_decoder(
+    mut decoder: MovieDecoder,
+    builder: &ThumbnailerBuilder,
+) -> Result<MovieDecoder, ThumbnailerError> {
+    // We actually have to decode a frame to get some metadata before we can start decoding for real
+    decoder.decode_video_frame()?;
+
+    if !decoder.embedded_metadata_is_available() {
+        if builder.frame_selection == FrameSelection::FirstKeyframe {
+            decoder.decode_until_key_frame()?;
+        } else if builder.seek_from_end.is_some() {
+            let position = builder.seek_position(&mut decoder)?;
+            decoder.seek_exact(position)?;
+        } else {
+            let position = builder.seek_position(&mut decoder)?;
+            match decoder.seek(position) {
+                // Falls back to the last frame that could be decoded before the position
+                Err(ThumbnailerError::TruncatedFile) if builder.allow_partial => {
+                    decoder.seek_exact(position)?
+                }
+                result => result?,
+            }
+        }
+    }
+
+    Ok(decoder)
+}
+
+/// `ThumbnailerBuilder` struct holds data to build a `Thumbnailer` struct, exposing many methods
+/// to configure how a thumbnail must be generated.
+#[derive(Debug, Clone)]
+pub struct ThumbnailerBuilder {
+    maintain_aspect_ratio: bool,
+    size: ThumbnailSize,
+    seek_percentage: f32,
+    seek_time: Option<Duration>,
+    seek_frame_percentage: Option<f32>,
+    seek_from_end: Option<Duration>,
+    skip_intro: Duration,
+    max_seek_decode_frames: Option<u32>,
+    assume_duration: Option<Duration>,
+    quality: f32,
+    quality_preset: Option<QualityPreset>,
+    raw_quality: Option<f32>,
+    target_max_bytes: Option<usize>,
+    prefer_embedded_metadata: bool,
+    strict_embedded: bool,
+    preferred_art_type: Option<ArtType>,
+    with_film_strip: bool,
+    film_strip_config: FilmStripConfig,
+    burn_subtitles: bool,
+    max_source_dimension: Option<u32>,
+    rotation_handling: RotationHandling,
+    frame_selection: FrameSelection,
+    atomic_write: bool,
+    temp_dir: Option<PathBuf>,
+    write_retries: u32,
+    crop: Option<Crop>,
+    blank_frame_detection: Option<BlankFrameDetection>,
+    network_retries: u32,
+    network_timeout: Option<Duration>,
+    open_timeout: Option<Duration>,
+    auto_crop_black_bars: bool,
+    black_bar_threshold: u8,
+    frame_cache_size: usize,
+    frame_cache_tolerance: Duration,
+    runtime: Option<Handle>,
+    ffmpeg_log_level: Option<LogLevel>,
+    preserve_icc: bool,
+    embed_metadata: bool,
+    allow_partial: bool,
+    denoise_strength: f32,
+    dither: bool,
+    color_adjustment: ColorAdjustment,
+    pad_to_square: bool,
+    pad_color: [u8; 3],
+    play_button_overlay: bool,
+    play_button: PlayButton,
+    burn_timestamp: bool,
+    timestamp_corner: Corner,
+    #[cfg(feature = "face-detect")]
+    face_detection_model: Option<PathBuf>,
+    #[cfg(feature = "phash")]
+    with_phash: bool,
+    #[cfg(feature = "phash")]
+    with_ahash: bool,
+    #[cfg(feature = "webp")]
+    webp_near_lossless: Option<u8>,
+    #[cfg(feature = "jpeg")]
+    jpeg_progressive: bool,
+    raw_channel_order: ChannelOrder,
+    raw_row_order: RowOrder,
+    custom_encoder: Option<CustomEncoder>,
+    frame_selector: Option<CustomFrameSelector>,
+    parallelism: Option<usize>,
+}
+
+impl Default for ThumbnailerBuilder {
+    fn default() -> Self {
+        Self {
+            maintain_aspect_ratio: true,
+            size: ThumbnailSize::Size(128),
+            seek_percentage: 0.1,
+            seek_time: None,
+            seek_frame_percentage: None,
+            seek_from_end: None,
+            skip_intro: Duration::ZERO,
+            max_seek_decode_frames: None,
+            assume_duration: None,
+            quality: 80.0,
+            quality_preset: None,
+            raw_quality: None,
+            target_max_bytes: None,
+            prefer_embedded_metadata: true,
+            strict_embedded: false,
+            preferred_art_type: None,
+            with_film_strip: true,
+            film_strip_config: FilmStripConfig::default(),
+            burn_subtitles: false,
+            max_source_dimension: None,
+            rotation_handling: RotationHandling::Physical,
+            frame_selection: FrameSelection::Seek,
+            atomic_write: true,
+            temp_dir: None,
+            write_retries: 0,
+            crop: None,
+            blank_frame_detection: None,
+            network_retries: 0,
+            network_timeout: None,
+            open_timeout: None,
+            auto_crop_black_bars: false,
+            black_bar_threshold: 24,
+            frame_cache_size: 4,
+            frame_cache_tolerance: Duration::ZERO,
+            runtime: None,
+            ffmpeg_log_level: None,
+            preserve_icc: false,
+            embed_metadata: false,
+            allow_partial: false,
+            denoise_strength: 0.0,
+            dither: false,
+            color_adjustment: ColorAdjustment::default(),
+            pad_to_square: false,
+            pad_color: [0, 0, 0],
+            play_button_overlay: false,
+            play_button: PlayButton::default(),
+            burn_timestamp: false,
+            timestamp_corner: Corner::BottomRight,
+            #[cfg(feature = "face-detect")]
+            face_detection_model: None,
+            #[cfg(feature = "phash")]
+            with_phash: false,
+            #[cfg(feature = "phash")]
+            with_ahash: false,
+            #[cfg(feature = "webp")]
+            webp_near_lossless: None,
+            #[cfg(feature = "jpeg")]
+            jpeg_progressive: false,
+            raw_channel_order: ChannelOrder::Rgb,
+            raw_row_order: RowOrder::TopDown,
+            custom_encoder: None,
+            frame_selector: None,
+            parallelism: None,
+        }
+    }
+}
+
+impl ThumbnailerBuilder {
+    /// Creates a new `ThumbnailerBuilder` with default values:
+    /// - `maintain_aspect_ratio`: true
+    /// - `size`: 128 pixels
+    /// - `seek_percentage`: 10%
+    /// - `seek_time`: none
+    /// - `seek_frame_percentage`: none
+    /// - `seek_from_end`: none
+    /// - `skip_intro`: none
+    /// - `max_seek_decode_frames`: no limit
+    /// - `assume_duration`: none, the input being probed for it
+    /// - `quality`: 80
+    /// - `quality_preset`: none, `quality` being used
+    /// - `target_max_bytes`: no limit
+    /// - `prefer_embedded_metadata`: true
+    /// - `strict_embedded`: false
+    /// - `preferred_art_type`: none, the first embedded picture being used
+    /// - `with_film_strip`: true
+    /// - `film_strip_config`: strips on the left and right edges
+    /// - `burn_subtitles`: false
+    /// - `max_source_dimension`: no limit
+    /// - `rotation_handling`: `RotationHandling::Physical`
+    /// - `frame_selection`: `FrameSelection::Seek`
+    /// - `atomic_write`: true
+    /// - `temp_dir`: the output directory
+    /// - `write_retries`: 0
+    /// - `crop`: none
+    /// - `skip_blank_frames`: disabled
+    /// - `network_retries`: 0
+    /// - `network_timeout`: FFmpeg's default
+    /// - `open_timeout`: none
+    /// - `auto_crop_black_bars`: false
+    /// - `black_bar_threshold`: 24
+    /// - `frame_cache_size`: 4
+    /// - `frame_cache_tolerance`: none
+    /// - `runtime`: the runtime of the caller
+    /// - `ffmpeg_log_level`: `None`, FFmpeg's log level being left as is
+    /// - `preserve_icc`: false
+    /// - `embed_metadata`: false
+    /// - `allow_partial`: false
+    /// - `denoise`: disabled
+    /// - `dither`: false
+    /// - `brightness`: 0.0
+    /// - `gamma`: 1.0
+    /// - `pad_to_square`: false
+    /// - `pad_color`: black
+    /// - `play_button_overlay`: false
+    /// - `play_button_size`: 0.25
+    /// - `play_button_opacity`: 0.8
+    /// - `burn_timestamp`: false
+    /// - `timestamp_corner`: `Corner::BottomRight`
+    /// - `face_detection_model`: none
+    /// - `with_phash`: false
+    /// - `with_ahash`: false
+    /// - `webp_near_lossless`: lossy encoding
+    /// - `jpeg_progressive`: false
+    /// - `raw_channel_order`: RGB
+    /// - `raw_row_order`: top-down
+    /// - `custom_encoder`: none
+    /// - `frame_selector`: none, `frame_selection` being used
+    /// - `parallelism`: the number of CPUs
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// To respect or not the aspect ratio from the video file in the generated thumbnail
+    pub fn maintain_aspect_ratio(mut self, maintain_aspect_ratio: bool) -> Self {
+        self.maintain_aspect_ratio = maintain_aspect_ratio;
+        self
+    }
+
+    /// To set a thumbnail size, respecting or not its aspect ratio, according to `maintain_aspect_ratio` value
+    pub fn size(mut self, size: u32) -> Self {
+        self.size = ThumbnailSize::Size(size);
+        self
+    }
+
+    /// To specify width and height of the thumbnail
+    pub fn width_and_height(mut self, width: u32, height: u32) -> Self {
+        self.size = ThumbnailSize::Dimensions { width, height };
+        self
+    }
+
+    /// Seek percentage must be a value between 0.0 and 1.0. For animated GIF and PNG inputs, it
+    /// applies to the frame count, e.g. 0.5 selecting the middle frame
+    pub fn seek_percentage(mut self, seek_percentage: f32) -> Result<Self, ThumbnailerError> {
+        if !(0.0..=1.0).contains(&seek_percentage) {
+            return Err(ThumbnailerError::InvalidSeekPercentage(seek_percentage));
+        }
+        self.seek_percentage = seek_percentage;
+        Ok(self)
+    }
+
+    /// Seeks to the absolute `seek_time` when it is within the video duration, falling back to the
+    /// seek percentage for shorter videos
+    pub fn seek_time(mut self, seek_time: Duration) -> Self {
+        self.seek_time = Some(seek_time);
+        self
+    }
+
+    /// Seeks to the frame at `seek_frame_percentage`, in range [0.0, 1.0], of the frame count
+    /// rather than of the duration, which is more predictable for variable frame rate videos.
+    /// `skip_intro` doesn't apply, and the seek percentage is used instead when the container
+    /// doesn't record the frame count. `seek_time` takes precedence when set
+    pub fn seek_frame_percentage(
+        mut self,
+        seek_frame_percentage: f32,
+    ) -> Result<Self, ThumbnailerError> {
+        if !(0.0..=1.0).contains(&seek_frame_percentage) {
+            return Err(ThumbnailerError::InvalidSeekPercentage(
+                seek_frame_percentage,
+            ));
+        }
+        self.seek_frame_percentage = Some(seek_frame_percentage);
+        Ok(self)
+    }
+
+    /// Seeks to `seek_from_end` before the end of the video, e.g. `Duration::ZERO` for its last
+    /// frame, decoding forward from the previous key frame to reach the exact frame. Takes
+    /// precedence over the seek percentages, `seek_time` still coming first when set
+    pub fn seek_from_end(mut self, seek_from_end: Duration) -> Self {
+        self.seek_from_end = Some(seek_from_end);
+        self
+    }
+
+    /// Skips the first `skip_intro` of the video, the seek percentage being then taken over the
+    /// remaining duration. Videos shorter than `skip_intro` are sought to their end
+    pub fn skip_intro(mut self, skip_intro: Duration) -> Self {
+        self.skip_intro = skip_intro;
+        self
+    }
+
+    /// Bounds the cost of precise seeks, such as the ones of `seek_from_end`: when reaching the
+    /// exact position would take decoding more than `max_seek_decode_frames` frames past the key
+    /// frame before it, the frame reached after that many is used instead
+    pub fn max_seek_decode_frames(mut self, max_seek_decode_frames: u32) -> Self {
+        self.max_seek_decode_frames = Some(max_seek_decode_frames);
+        self
+    }
+
+    /// Trusts `assume_duration` as the duration of every input, e.g. when already known from a
+    /// previous run, for the seek computation. This skips probing the streams, which decodes their
+    /// start, unless the container header lacks the video dimensions or pixel format
+    pub fn assume_duration(mut self, assume_duration: Duration) -> Self {
+        self.assume_duration = Some(assume_duration);
+        self
+    }
+
+    /// Quality must be a value between 0.0 and 100.0, see `OutputFormat::native_quality` for how
+    /// it maps to each encoder
+    pub fn quality(mut self, quality: f32) -> Result<Self, ThumbnailerError> {
+        if !(0.0..=100.0).contains(&quality) {
+            return Err(ThumbnailerError::InvalidQuality(quality));
+        }
+        self.quality = quality;
+        self.quality_preset = None;
+        Ok(self)
+    }
+
+    /// Sets the quality from `quality_preset` rather than a number, mapped to each output format
+    /// by `QualityPreset::quality`. `QualityPreset::Lossless` also encodes WebP losslessly. The
+    /// last of `quality` and `quality_preset` set is the one used
+    pub fn quality_preset(mut self, quality_preset: QualityPreset) -> Self {
+        self.quality_preset = Some(quality_preset);
+        self
+    }
+
+    /// Encoder-native quality value, passed through untouched to the encoder instead of mapping
+    /// `quality`. It isn't validated, so it must be in the range the encoder expects
+    pub fn raw_quality(mut self, raw_quality: f32) -> Self {
+        self.raw_quality = Some(raw_quality);
+        self
+    }
+
+    /// Lowers the quality of lossy formats as little as needed for thumbnails to fit in
+    /// `target_max_bytes`, the quality used being reported in `OutputContainer::quality`.
+    /// Thumbnails that can't fit even at the lowest quality fail, while lossless formats and
+    /// `raw_quality` are left untouched
+    pub fn target_max_bytes(mut self, target_max_bytes: usize) -> Self {
+        self.target_max_bytes = Some(target_max_bytes);
+        self
+    }
+
+    /// To use embedded metadata in the video file, if available, instead of getting a frame as a
+    /// thumbnail
+    pub fn prefer_embedded_metadata(mut self, prefer_embedded_metadata: bool) -> Self {
+        self.prefer_embedded_metadata = prefer_embedded_metadata;
+        self
+    }
+
+    /// If `strict_embedded` is false, inputs opened by path whose embedded metadata fails to
+    /// decode, such as corrupt cover art, are thumbnailed from their video stream instead. If
+    /// true, the decoding error is returned
+    pub fn strict_embedded(mut self, strict_embedded: bool) -> Self {
+        self.strict_embedded = strict_embedded;
+        self
+    }
+
+    /// Kind of embedded picture used by `prefer_embedded_metadata` when a file has several, such
+    /// as front and back covers, falling back to the first one when none is of `art_type`
+    pub fn preferred_art_type(mut self, art_type: ArtType) -> Self {
+        self.preferred_art_type = Some(art_type);
+        self
+    }
+
+    /// If `with_film_strip` is true, a film strip will be added to the thumbnail borders
+    pub fn with_film_strip(mut self, with_film_strip: bool) -> Self {
+        self.with_film_strip = with_film_strip;
+        self
+    }
+
+    /// To choose the edges the film strip is drawn on, and its maximum width, when
+    /// `with_film_strip` is true
+    pub fn film_strip_config(mut self, film_strip_config: FilmStripConfig) -> Self {
+        self.film_strip_config = film_strip_config;
+        self
+    }
+
+    /// If `burn_subtitles` is true, the subtitle active at the seek time, if any, is rendered on the
+    /// thumbnail. This requires FFmpeg to be built with libass
+    pub fn burn_subtitles(mut self, burn_subtitles: bool) -> Self {
+        self.burn_subtitles = burn_subtitles;
+        self
+    }
+
+    /// Rejects videos whose coded width or height exceeds `max_source_dimension` with
+    /// `ThumbnailerError::SourceTooLarge`, before allocating any frame buffer
+    pub fn max_source_dimension(mut self, max_source_dimension: u32) -> Self {
+        self.max_source_dimension = Some(max_source_dimension);
+        self
+    }
+
+    /// To choose whether the video rotation is applied to the thumbnail pixels or left to viewers
+    /// through an Exif orientation
+    pub fn rotation_handling(mut self, rotation_handling: RotationHandling) -> Self {
+        self.rotation_handling = rotation_handling;
+        self
+    }
+
+    /// To choose how the frame used as a thumbnail is picked in the video stream
+    pub fn frame_selection(mut self, frame_selection: FrameSelection) -> Self {
+        self.frame_selection = frame_selection;
+        self
+    }
+
+    /// If `atomic_write` is true, `Thumbnailer::process` writes to a temporary file in the output
+    /// directory, renamed over the output path once complete, so that concurrent readers never see
+    /// a partial thumbnail
+    pub fn atomic_write(mut self, atomic_write: bool) -> Self {
+        self.atomic_write = atomic_write;
+        self
+    }
+
+    /// Directory of the temporary files written with `atomic_write`, instead of the output
+    /// directory, e.g. when the latter is a slow or restricted network mount. When it's on another
+    /// filesystem than the output, the temporary file is copied rather than renamed over the
+    /// output path, which readers may then see partially written
+    pub fn temp_dir(mut self, temp_dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = Some(temp_dir.into());
+        self
+    }
+
+    /// Number of times `Thumbnailer::process` retries writing the thumbnail after a transient I/O
+    /// error, such as `EAGAIN` on network filesystems, waiting 100ms before the first retry and
+    /// twice as long before each next one. The last error is returned when every retry fails.
+    /// Waiting relies on tokio's timer, so the runtime must have time enabled when retrying
+    pub fn write_retries(mut self, write_retries: u32) -> Self {
+        self.write_retries = write_retries;
+        self
+    }
+
+    /// Trims fractions of the displayed picture from each of its edges before scaling, e.g. to
+    /// leave out a ticker burned in the bottom of the video. Each fraction must be in range
+    /// [0.0, 1.0), and opposite fractions must leave a region between them
+    pub fn crop(
+        mut self,
+        top: f32,
+        bottom: f32,
+        left: f32,
+        right: f32,
+    ) -> Result<Self, ThumbnailerError> {
+        let crop = Crop {
+            top,
+            bottom,
+            left,
+            right,
+        };
+        if !crop.is_valid() {
+            return Err(ThumbnailerError::InvalidCrop {
+                top,
+                bottom,
+                left,
+                right,
+            });
+        }
+        self.crop = Some(crop);
+        Ok(self)
+    }
+
+    /// Skips blank frames, such as fades to black, at the seek position according to
+    /// `blank_frame_detection`. Only applies to `FrameSelection::Seek`
+    pub fn skip_blank_frames(
+        mut self,
+        blank_frame_detection: BlankFrameDetection,
+    ) -> Result<Self, ThumbnailerError> {
+        blank_frame_detection.validate()?;
+        self.blank_frame_detection = Some(blank_frame_detection);
+        Ok(self)
+    }
+
+    /// Number of times a network input, such as an HTTP URL, is reconnected to after a network
+    /// error or a 5xx response. Requires FFmpeg 7.0 or later
+    pub fn network_retries(mut self, network_retries: u32) -> Self {
+        self.network_retries = network_retries;
+        self
+    }
+
+    /// Timeout of each I/O operation on a network input, such as an HTTP URL
+    pub fn network_timeout(mut self, network_timeout: Duration) -> Self {
+        self.network_timeout = Some(network_timeout);
+        self
+    }
+
+    /// Time allowed to open the input and probe its streams, network connection included, after
+    /// which processing fails with `ThumbnailerError::OpenTimeout`. Decoding isn't bounded by it
+    pub fn open_timeout(mut self, open_timeout: Duration) -> Self {
+        self.open_timeout = Some(open_timeout);
+        self
+    }
+
+    /// If `auto_crop_black_bars` is true, uniform black bars around the picture, such as a
+    /// letterbox, are detected on the decoded frame and cropped before scaling. The `crop`
+    /// fractions, if any, are then relative to the remaining picture
+    pub fn auto_crop_black_bars(mut self, auto_crop_black_bars: bool) -> Self {
+        self.auto_crop_black_bars = auto_crop_black_bars;
+        self
+    }
+
+    /// Luma value, in range [0, 255], up to which the mean of a row or column of pixels makes it
+    /// part of a black bar when `auto_crop_black_bars` is enabled
+    pub fn black_bar_threshold(mut self, black_bar_threshold: u8) -> Self {
+        self.black_bar_threshold = black_bar_threshold;
+        self
+    }
+
+    /// Number of scaled frames kept by a `FrameSource` to answer repeated requests without
+    /// decoding again, 0 disabling the cache
+    pub fn frame_cache_size(mut self, frame_cache_size: usize) -> Self {
+        self.frame_cache_size = frame_cache_size;
+        self
+    }
+
+    /// Maximum distance between the requested timestamp and the one of a cached frame for
+    /// `FrameSource::frame_at` to return the cached frame instead of seeking
+    pub fn frame_cache_tolerance(mut self, frame_cache_tolerance: Duration) -> Self {
+        self.frame_cache_tolerance = frame_cache_tolerance;
+        self
+    }
+
+    /// Runs the blocking decoding and encoding work on the blocking thread pool of `runtime`
+    /// rather than of the caller's runtime, e.g. to isolate it in a runtime whose
+    /// `max_blocking_threads` bounds the number of concurrent thumbnails
+    pub fn runtime(mut self, runtime: Handle) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Level of the messages FFmpeg logs while decoding. FFmpeg's log level is global to the
+    /// process rather than per decode, so it's set anew by each operation of this `Thumbnailer`,
+    /// overriding the level set by other `Thumbnailer`s or by the application itself. Left as is
+    /// unless set
+    pub fn ffmpeg_log_level(mut self, ffmpeg_log_level: LogLevel) -> Self {
+        self.ffmpeg_log_level = Some(ffmpeg_log_level);
+        self
+    }
+
+    /// If `preserve_icc` is true, the ICC color profile of the source, if any, is embedded in
+    /// thumbnails so that color managed viewers display wide gamut content correctly
+    pub fn preserve_icc(mut self, preserve_icc: bool) -> Self {
+        self.preserve_icc = preserve_icc;
+        self
+    }
+
+    /// If `embed_metadata` is true, the source path, the position of the frame and the crate
+    /// version are embedded in thumbnails, as text chunks in PNG and XMP metadata in WebP and
+    /// JPEG, to trace them back to their origin
+    pub fn embed_metadata(mut self, embed_metadata: bool) -> Self {
+        self.embed_metadata = embed_metadata;
+        self
+    }
+
+    /// Denoises grainy sources before scaling with FFmpeg's `hqdn3d` filter, `strength` being its
+    /// spatial luma strength, 4.0 being a light denoise. 0.0 disables the filter
+    pub fn denoise(mut self, strength: f32) -> Result<Self, ThumbnailerError> {
+        if !strength.is_finite() || strength < 0.0 {
+            return Err(ThumbnailerError::InvalidDenoiseStrength(strength));
+        }
+        self.denoise_strength = strength;
+        Ok(self)
+    }
+
+    /// If `dither` is true, frames are converted to RGB at full precision with error diffusion
+    /// dithering, and WebP thumbnails are dithered again when converted to YUV, reducing the
+    /// banding of smooth gradients such as skies, at the cost of slightly larger outputs
+    pub fn dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Offset in range [-1.0, 1.0] added to every RGB channel of the scaled thumbnail, 1.0 being
+    /// the full channel range, e.g. to lift systematically dark sources. Applied after `gamma`
+    pub fn brightness(mut self, brightness: f32) -> Result<Self, ThumbnailerError> {
+        if !(-1.0..=1.0).contains(&brightness) {
+            return Err(ThumbnailerError::InvalidBrightness(brightness));
+        }
+        self.color_adjustment.brightness = brightness;
+        Ok(self)
+    }
+
+    /// Gamma correction of the scaled thumbnail, every RGB channel being mapped to
+    /// `value^(1 / gamma)`, so that values above 1.0 brighten the shadows
+    pub fn gamma(mut self, gamma: f32) -> Result<Self, ThumbnailerError> {
+        if !gamma.is_finite() || gamma <= 0.0 {
+            return Err(ThumbnailerError::InvalidGamma(gamma));
+        }
+        self.color_adjustment.gamma = gamma;
+        Ok(self)
+    }
+
+    /// If `pad_to_square` is true, scaled thumbnails are centered on a square canvas filled with
+    /// `pad_color`, as wide as their longest side, so that thumbnails of any aspect ratio line up
+    /// in a grid while the whole frame stays visible
+    pub fn pad_to_square(mut self, pad_to_square: bool) -> Self {
+        self.pad_to_square = pad_to_square;
+        self
+    }
+
+    /// RGB color of the canvas of `pad_to_square`
+    pub fn pad_color(mut self, pad_color: [u8; 3]) -> Self {
+        self.pad_color = pad_color;
+        self
+    }
+
+    /// If `play_button_overlay` is true, a play button is drawn centered on scaled thumbnails, as
+    /// a white triangle on a darkened disc, so that they are recognizable as videos wherever they
+    /// are shared. Thumbnails being opaque, it is blended into their pixels. Sprite sheet cells
+    /// are left as is
+    pub fn play_button_overlay(mut self, play_button_overlay: bool) -> Self {
+        self.play_button_overlay = play_button_overlay;
+        self
+    }
+
+    /// Diameter of the disc of `play_button_overlay`, as a fraction in range (0.0, 1.0] of the
+    /// shorter side of the thumbnail
+    pub fn play_button_size(mut self, play_button_size: f32) -> Result<Self, ThumbnailerError> {
+        if !(0.0..=1.0).contains(&play_button_size) || play_button_size == 0.0 {
+            return Err(ThumbnailerError::InvalidPlayButtonSize(play_button_size));
+        }
+        self.play_button.size = play_button_size;
+        Ok(self)
+    }
+
+    /// Opacity in range [0.0, 1.0] of the triangle of `play_button_overlay`, its disc being half
+    /// as opaque
+    pub fn play_button_opacity(
+        mut self,
+        play_button_opacity: f32,
+    ) -> Result<Self, ThumbnailerError> {
+        if !(0.0..=1.0).contains(&play_button_opacity) {
+            return Err(ThumbnailerError::InvalidOpacity(play_button_opacity));
+        }
+        self.play_button.opacity = play_button_opacity;
+        Ok(self)
+    }
+
+    /// If `burn_timestamp` is true, the position of the frame in the video stream is drawn as
+    /// `MM:SS.mmm` in `timestamp_corner` of scaled thumbnails, e.g. to check seek accuracy at a
+    /// glance. Frames without a position, such as embedded cover art, are left as is, as are
+    /// sprite sheet cells
+    pub fn burn_timestamp(mut self, burn_timestamp: bool) -> Self {
+        self.burn_timestamp = burn_timestamp;
+        self
+    }
+
+    /// Corner of the thumbnail `burn_timestamp` draws the timestamp in
+    pub fn timestamp_corner(mut self, timestamp_corner: Corner) -> Self {
+        self.timestamp_corner = timestamp_corner;
+        self
+    }
+
+    /// Inputs ending before their announced duration, such as files still being downloaded, fail
+    /// with `ThumbnailerError::TruncatedFile` when the seek position is past their end. If
+    /// `allow_partial` is true, the last frame that could be decoded is used instead
+    pub fn allow_partial(mut self, allow_partial: bool) -> Self {
+        self.allow_partial = allow_partial;
+        self
+    }
+
+    /// Path of the SeetaFace frontal face model, such as `seeta_fd_frontal_v1.0.bin`, used by
+    /// `FrameSelection::WithFace` to find faces in candidate frames
+    #[cfg(feature = "face-detect")]
+    pub fn face_detection_model(mut self, face_detection_model: impl Into<PathBuf>) -> Self {
+        self.face_detection_model = Some(face_detection_model.into());
+        self
+    }
+
+    /// If `with_phash` is true, a 64-bit perceptual hash of each thumbnail's frame is computed
+    /// and returned in `OutputContainer::phash`, e.g. to find near-identical videos
+    #[cfg(feature = "phash")]
+    pub fn with_phash(mut self, with_phash: bool) -> Self {
+        self.with_phash = with_phash;
+        self
+    }
+
+    /// If `with_ahash` is true, a 64-bit average hash of each thumbnail's frame is computed and
+    /// returned in `OutputContainer::ahash`, sharing its pass over the frame with the perceptual
+    /// hash when both are enabled
+    #[cfg(feature = "phash")]
+    pub fn with_ahash(mut self, with_ahash: bool) -> Self {
+        self.with_ahash = with_ahash;
+        self
+    }
+
+    /// Encodes WebP thumbnails losslessly, after near-lossless preprocessing of level
+    /// `webp_near_lossless` in range [0, 100], 0 being the strongest preprocessing and 100 plain
+    /// lossless encoding. Crisper than lossy encoding on screenshots and smaller than plain
+    /// lossless, the `quality` then only setting the compression effort
+    #[cfg(feature = "webp")]
+    pub fn webp_near_lossless(mut self, webp_near_lossless: u8) -> Result<Self, ThumbnailerError> {
+        if webp_near_lossless > 100 {
+            return Err(ThumbnailerError::InvalidNearLossless(webp_near_lossless));
+        }
+        self.webp_near_lossless = Some(webp_near_lossless);
+        Ok(self)
+    }
+
+    /// If `jpeg_progressive` is true, JPEG thumbnails are encoded progressively so that they render
+    /// incrementally while loading, at the cost of a few bytes over baseline encoding
+    #[cfg(feature = "jpeg")]
+    pub fn jpeg_progressive(mut self, jpeg_progressive: bool) -> Self {
+        self.jpeg_progressive = jpeg_progressive;
+        self
+    }
+
+    /// Order of the channels of `OutputFormat::Raw` pixels, e.g. BGRA for Direct3D textures
+    pub fn raw_channel_order(mut self, raw_channel_order: ChannelOrder) -> Self {
+        self.raw_channel_order = raw_channel_order;
+        self
+    }
+
+    /// Order of the rows of `OutputFormat::Raw` outputs
+    pub fn raw_row_order(mut self, raw_row_order: RowOrder) -> Self {
+        self.raw_row_order = raw_row_order;
+        self
+    }
+
+    /// Encodes every thumbnail with `custom_encoder` instead of the built-in encoders, the
+    /// requested `OutputFormat` then being only reported in the `OutputContainer`
+    pub fn custom_encoder(mut self, custom_encoder: Box<dyn Encoder>) -> Self {
+        self.custom_encoder = Some(CustomEncoder(custom_encoder.into()));
+        self
+    }
+
+    /// Chooses the thumbnail frame with `frame_selector` instead of `frame_selection`, for
+    /// videos without embedded metadata used instead
+    pub fn frame_selector(mut self, frame_selector: Box<dyn FrameSelector>) -> Self {
+        self.frame_selector = Some(CustomFrameSelector(frame_selector.into()));
+        self
+    }
+
+    /// Maximum number of encodings or sprite sheet rows processed at once, each holding its own
+    /// frame buffers, so that lowering it bounds the peak memory use. At least one
+    pub fn parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = Some(parallelism.max(1));
+        self
+    }
+
+    /// Number of threads operations are spread over, as set by `parallelism`
+    fn thread_count(&self) -> usize {
+        self.parallelism.unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1)
+        })
+    }
+
+    /// Builds a `Thumbnailer` struct
+    pub fn build(self) -> Thumbnailer {
+        Thumbnailer { builder: self }
+    }
+
+    pub(crate) fn spawn_blocking<F, R>(&self, f: F) -> JoinHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let ffmpeg_log_level = self.ffmpeg_log_level;
+        let f = move || {
+            if let Some(ffmpeg_log_level) = ffmpeg_log_level {
+                logging::set_log_level(ffmpeg_log_level);
+            }
+            f()
+        };
+        match &self.runtime {
+            Some(runtime) => runtime.spawn_blocking(f),
+            None => tokio::task::spawn_blocking(f),
+        }
+    }
+
+    fn seek_position(&self, decoder: &mut MovieDecoder) -> Result<Duration, ThumbnailerError> {
+        let video_duration = decoder.get_video_duration();
+        if let Some(seek_time) = self.seek_time {
+            if seek_time < video_duration {
+                return Ok(seek_time);
+            }
+        }
+
+        if let Some(seek_from_end) = self.seek_from_end {
+            return Ok(video_duration.saturating_sub(seek_from_end));
+        }
+
+        // Animated images have few frames and durations too short for whole seconds, so the seek
+        // percentage is always applied to their frame count
+        if decoder.is_animated_image() {
+            let percentage = self.seek_frame_percentage.unwrap_or(self.seek_percentage);
+            let frame_count = decoder.count_frames()?;
+            if frame_count > 0 {
+                let frame_index = ((frame_count - 1) as f64 * percentage as f64).round();
+                if let Some(position) = decoder.get_frame_position(frame_index as u64)? {
+                    return Ok(position);
+                }
+            }
+        }
+
+        if let Some(seek_frame_percentage) = self.seek_frame_percentage {
+            if let Some(frame_count) = decoder.get_frame_count() {
+                let frame_index = ((frame_count - 1) as f64 * seek_frame_percentage as f64).round();
+                if let Some(position) = decoder.get_frame_position(frame_index as u64)? {
+                    return Ok(position);
+                }
+            }
+        }
+
+        let duration = video_duration.as_secs() as f32;
+        let skip_intro = self.skip_intro.as_secs_f32().min(duration);
+
+        Ok(Duration::from_secs(
+            (skip_intro + (duration - skip_intro) * self.seek_percentage).round() as u64,
+        ))
+    }
+
+    /// `count` positions evenly spread over the video once the intro is skipped, each in the
+    /// middle of its own interval so that neither the very first nor the very last frame is used
+    fn interval_positions(&self, video_duration: Duration, count: usize) -> Vec<Duration> {
+        let start = self.skip_intro.min(video_duration);
+        let span = (video_duration - start).as_nanos();
+
+        (0..count as u128)
+            .map(|index| {
+                start + Duration::from_nanos((span * (2 * index + 1) / (2 * count as u128)) as u64)
+            })
+            .collect()
+    }
+
+    fn decoder_options(&self) -> DecoderOptions {
+        DecoderOptions {
+            prefer_embedded_metadata: self.prefer_embedded_metadata,
+            burn_subtitles: self.burn_subtitles,
+            max_source_dimension: self.max_source_dimension,
+            rotation_handling: self.rotation_handling,
+            crop: self.crop,
+            network_retries: self.network_retries,
+            network_timeout: self.network_timeout,
+            open_timeout: self.open_timeout,
+            black_bar_threshold: if self.auto_crop_black_bars {
+                Some(self.black_bar_threshold)
+            } else {
+                None
+            },
+            preserve_icc: self.preserve_icc,
+            embed_metadata: self.embed_metadata,
+            denoise_strength: (self.denoise_strength > 0.0).then_some(self.denoise_strength),
+            dither: self.dither,
+            max_seek_decode_frames: self.max_seek_decode_frames,
+            assume_duration: self.assume_duration,
+            preferred_art_type: self.preferred_art_type,
+            #[cfg(unix)]
+            input_fd: None,
+            thumbnail_size: Some(self.size),
+            maintain_aspect_ratio: self.maintain_aspect_ratio,
+        }
+    }
+
+    fn select_video_frame(
+        &self,
+        decoder: &mut MovieDecoder,
+        video_frame: &mut VideoFrame,
+    ) -> Result<(), ThumbnailerError> {
+        if let Some(CustomFrameSelector(frame_selector)) = &self.frame_selector {
+            if !decoder.embedded_metadata_is_available() {
+                *video_frame = frame_selector.select(decoder)?;
+                self.finish_video_frame(video_frame);
+                video_frame.decode_stats = decoder.get_decode_stats();
+                return Ok(());
+            }
+        }
+
+        match self.frame_selection {
+            FrameSelection::Brightest { window } if !decoder.embedded_metadata_is_available() => {
+                self.extract_brightest_video_frame(decoder, window, video_frame)?
+            }
+            FrameSelection::AverageOfN { count } if !decoder.embedded_metadata_is_available() => {
+                self.extract_average_video_frame(decoder, count, video_frame)?
+            }
+            #[cfg(feature = "face-detect")]
+            FrameSelection::WithFace { window } if !decoder.embedded_metadata_is_available() => {
+                self.extract_video_frame_with_face(decoder, window, video_frame)?
+            }
+            FrameSelection::Seek if !decoder.embedded_metadata_is_available() => {
+                match self.blank_frame_detection {
+                    Some(detection) => {
+                        self.extract_non_blank_video_frame(decoder, detection, video_frame)?
+                    }
+                    None => self.extract_video_frame(decoder, video_frame)?,
+                }
+            }
+            _ => self.extract_video_frame(decoder, video_frame)?,
+        }
+        // The selected frame may have been decoded before other candidates
+        video_frame.decode_stats = decoder.get_decode_stats();
+        Ok(())
+    }
+
+    fn extract_non_blank_video_frame(
+        &self,
+        decoder: &mut MovieDecoder,
+        detection: BlankFrameDetection,
+        video_frame: &mut VideoFrame,
+    ) -> Result<(), ThumbnailerError> {
+        decoder.get_scaled_video_frame(Some(self.size), self.maintain_aspect_ratio, video_frame)?;
+        let mut best_luminance = detection.luminance(video_frame);
+
+        let duration = decoder.get_video_duration();
+        let start = self.seek_position(decoder)?;
+        let step = duration.mul_f32(detection.step_percentage);
+        let mut candidate = VideoFrame::default();
+        for attempt in 1..=detection.max_attempts {
+            let position = start + step * attempt;
+            if !detection.is_blank(best_luminance) || step.is_zero() || position > duration {
+                break;
+            }
+
+            decoder.seek(position)?;
+            decoder.get_scaled_video_frame(
+                Some(self.size),
+                self.maintain_aspect_ratio,
+                &mut candidate,
+            )?;
+
+            let luminance = detection.luminance(&candidate);
+            if luminance > best_luminance {
+                best_luminance = luminance;
+                mem::swap(video_frame, &mut candidate);
+            }
+        }
+
+        self.finish_video_frame(video_frame);
+
+        Ok(())
+    }
+
+    fn extract_brightest_video_frame(
+        &self,
+        decoder: &mut MovieDecoder,
+        window: Duration,
+        video_frame: &mut VideoFrame,
+    ) -> Result<(), ThumbnailerError> {
+        let duration = decoder.get_video_duration();
+        let start = self.seek_position(decoder)?;
+        let span = (start + window).min(duration).saturating_sub(start);
+        let samples = if span.is_zero() { 1 } else { BRIGHTEST_SAMPLES };
+
+        let mut candidate = VideoFrame::default();
+        let mut best_luminance = -1.0;
+        for sample in 0..samples {
+            decoder.seek(start + span * sample / (BRIGHTEST_SAMPLES - 1))?;
+            decoder.get_scaled_video_frame(
+                Some(self.size),
+                self.maintain_aspect_ratio,
+                &mut candidate,
+            )?;
+
+            let luminance = candidate.mean_luminance();
+            if luminance > best_luminance {
+                best_luminance = luminance;
+                mem::swap(video_frame, &mut candidate);
+            }
+        }
+
+        self.finish_video_frame(video_frame);
+
+        Ok(())
+    }
+
+    fn extract_average_video_frame(
+        &self,
+        decoder: &mut MovieDecoder,
+        count: usize,
+        video_frame: &mut VideoFrame,
+    ) -> Result<(), ThumbnailerError> {
+        let positions = self.interval_positions(decoder.get_video_duration(), count.max(1));
+
+        let mut accumulator = FrameAccumulator::default();
+        let mut candidate = VideoFrame::default();
+        for (index, &position) in positions.iter().enumerate() {
+            decoder.seek(position)?;
+            // The first frame holds the result, the others only being summed
+            let frame = if index == 0 {
+                &mut *video_frame
+            } else {
+                &mut candidate
+            };
+            decoder.get_scaled_video_frame(Some(self.size), self.maintain_aspect_ratio, frame)?;
+            accumulator.add(frame);
+        }
+        accumulator.average_into(video_frame);
+        if let Some(source_metadata) = &mut video_frame.source_metadata {
+            source_metadata.seek_time = None;
+        }
+
+        self.finish_video_frame(video_frame);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "face-detect")]
+    fn extract_video_frame_with_face(
+        &self,
+        decoder: &mut MovieDecoder,
+        window: Duration,
+        video_frame: &mut VideoFrame,
+    ) -> Result<(), ThumbnailerError> {
+        let model = self
+            .face_detection_model
+            .as_deref()
+            .ok_or(ThumbnailerError::MissingFaceDetectionModel)?;
+        let mut detector = face_detection::load_detector(model)?;
+
+        let duration = decoder.get_video_duration();
+        let start = self.seek_position(decoder)?;
+        let span = (start + window).min(duration).saturating_sub(start);
+        let samples = if span.is_zero() { 1 } else { FACE_SAMPLES };
+
+        let mut found_face = false;
+        for sample in 0..samples {
+            decoder.seek(start + span * sample / (FACE_SAMPLES - 1))?;
+            decoder.get_scaled_video_frame(
+                Some(self.size),
+                self.maintain_aspect_ratio,
+                video_frame,
+            )?;
+            if face_detection::contains_face(detector.as_mut(), video_frame) {
+                found_face = true;
+                break;
+            }
+        }
+
+        if !found_face {
+            decoder.seek(start)?;
+            decoder.get_scaled_video_frame(
+                Some(self.size),
+                self.maintain_aspect_ratio,
+                video_frame,
+            )?;
+        }
+
+        self.finish_video_frame(video_frame);
+
+        Ok(())
+    }
+
+    /// Samples `columns * rows` frames evenly across the video and composites them into a grid
+    fn extract_sprite_sheet(
+        &self,
+        video_file_path: PathBuf,
+        columns: u32,
+        rows: u32,
+        output_format: OutputFormat,
+    ) -> Result<SpriteSheet, ThumbnailerError> {
+        let frame_count = columns
+            .checked_mul(rows)
+            .ok_or(ThumbnailerError::InvalidGrid { columns, rows })?;
+        let mut decoder = open_video_stream_decoder(video_file_path, self)?;
+
+        // Decoding is sequential as seeking is stateful, compositing is done in parallel
+        let timestamps =
+            self.interval_positions(decoder.get_video_duration(), frame_count as usize);
+        let mut cells = Vec::with_capacity(timestamps.len());
+        let mut frame_timestamps = Vec::with_capacity(timestamps.len());
+        for &timestamp in &timestamps {
+            decoder.seek(timestamp)?;
+            frame_timestamps.push(decoder.get_frame_timestamp().unwrap_or(timestamp));
+            let mut cell = VideoFrame::default();
+            decoder.get_scaled_video_frame(
+                Some(self.size),
+                self.maintain_aspect_ratio,
+                &mut cell,
+            )?;
+            self.color_adjustment.apply(&mut cell);
+            cells.push(cell);
+        }
+
+        let sheet =
+            sprite_sheet::composite(&mut cells, columns, self.film_strip(), self.thread_count());
+        Ok(SpriteSheet {
+            columns,
+            rows,
+            cell_width: cells[0].width,
+            cell_height: cells[0].height,
+            timestamps,
+            frame_timestamps,
+            image: self.encode(&sheet, output_format)?,
+        })
+    }
+
+    #[cfg(feature = "png")]
+    fn stream_sprite_sheet<W: Write>(
+        &self,
+        video_file_path: PathBuf,
+        columns: u32,
+        rows: u32,
+        mut writer: W,
+    ) -> Result<(SpriteSheetLayout, W), ThumbnailerError> {
+        let frame_count = columns
+            .checked_mul(rows)
+            .ok_or(ThumbnailerError::InvalidGrid { columns, rows })?;
+        let mut decoder = open_video_stream_decoder(video_file_path, self)?;
+
+        let timestamps =
+            self.interval_positions(decoder.get_video_duration(), frame_count as usize);
+        let mut frame_timestamps = Vec::with_capacity(timestamps.len());
+        let mut cell_size = None;
+        let mut png_writer = None;
+        for row_timestamps in timestamps.chunks(columns as usize) {
+            let mut cells = Vec::with_capacity(columns as usize);
+            for &timestamp in row_timestamps {
+                decoder.seek(timestamp)?;
+                frame_timestamps.push(decoder.get_frame_timestamp().unwrap_or(timestamp));
+                let mut cell = VideoFrame::default();
+                decoder.get_scaled_video_frame(
+                    Some(self.size),
+                    self.maintain_aspect_ratio,
+                    &mut cell,
+                )?;
+                self.color_adjustment.apply(&mut cell);
+                cells.push(cell);
+            }
+
+            // The dimensions of the sheet are only known once the first cell is decoded
+            let (cell_width, cell_height) =
+                *cell_size.get_or_insert((cells[0].width as usize, cells[0].height as usize));
+            if png_writer.is_none() {
+                let (width, height) = (cell_width as u32)
+                    .checked_mul(columns)
+                    .zip((cell_height as u32).checked_mul(rows))
+                    .ok_or(ThumbnailerError::InvalidGrid { columns, rows })?;
+                let mut encoder = png::Encoder::new(&mut writer, width, height);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                png_writer = Some(encoder.write_header()?.into_stream_writer()?);
+            }
+            let band = sprite_sheet::composite_with_cell_size(
+                &mut cells,
+                columns,
+                (cell_width, cell_height),
+                self.film_strip(),
+                self.thread_count(),
+            );
+            if let Some(png_writer) = &mut png_writer {
+                png_writer.write_all(&band.data)?;
+            }
+        }
+        if let Some(png_writer) = png_writer {
+            png_writer.finish()?;
+        }
+
+        let (cell_width, cell_height) = cell_size.unwrap_or_default();
+        let layout = SpriteSheetLayout {
+            columns,
+            rows,
+            cell_width: cell_width as u32,
+            cell_height: cell_height as u32,
+            timestamps,
+            frame_timestamps,
+        };
+        Ok((layout, writer))
+    }
+
+    pub(crate) fn extract_video_frame(
+        &self,
+        decoder: &mut MovieDecoder,
+        video_frame: &mut VideoFrame,
+    ) -> Result<(), ThumbnailerError> {
+        decoder.get_scaled_video_frame(Some(self.size), self.maintain_aspect_ratio, video_frame)?;
+
+        self.finish_video_frame(video_frame);
+
+        Ok(())
+    }
+
+    fn film_strip(&self) -> Option<FilmStripConfig> {
+        self.with_film_strip.then_some(self.film_strip_config)
+    }
+
+    /// Hashes a selected frame, adjusts its colors, pads it to a square then draws the film
+    /// strip, the play button and the timestamp on it, as enabled
+    fn finish_video_frame(&self, video_frame: &mut VideoFrame) {
+        #[cfg(feature = "phash")]
+        {
+            (video_frame.phash, video_frame.ahash) =
+                phash::hashes(video_frame, self.with_phash, self.with_ahash);
+        }
+        self.color_adjustment.apply(video_frame);
+        if self.pad_to_square {
+            filters::pad_to_square(video_frame, self.pad_color);
+        }
+        if let Some(film_strip) = self.film_strip() {
+            film_strip_filter(video_frame, film_strip);
+        }
+        if self.play_button_overlay {
+            self.play_button.apply(video_frame);
+        }
+        if let Some(timestamp) = video_frame.timestamp.filter(|_| self.burn_timestamp) {
+            filters::burn_timestamp(video_frame, timestamp, self.timestamp_corner);
+        }
+    }
+
+    pub(crate) fn encode(
+        &self,
+        video_frame: &VideoFrame,
+        output_format: OutputFormat,
+    ) -> Result<OutputContainer, ThumbnailerError> {
+        if let Some(CustomEncoder(encoder)) = &self.custom_encoder {
+            let quality = self.quality_for(output_format);
+            let bytes = encoder
+                .encode(video_frame, self.raw_quality.unwrap_or(quality))
+                .map_err(ThumbnailerError::CustomEncodingError)?;
+            let mut container = OutputContainer::from(video_frame, output_format, bytes);
+            container.quality = self.raw_quality.is_none().then_some(quality);
+            return Ok(container);
+        }
+
+        let quality = self.quality_for(output_format);
+        let container = self.encode_with_quality(video_frame, output_format, quality)?;
+        match self.target_max_bytes {
+            Some(max_bytes) if container.bytes.len() > max_bytes && container.quality.is_some() => {
+                self.encode_within(video_frame, output_format, max_bytes)
+            }
+            _ => Ok(container),
+        }
+    }
+
+    /// Quality in range [0.0, 100.0] to encode `output_format` with, from `quality_preset` if set
+    fn quality_for(&self, output_format: OutputFormat) -> f32 {
+        self.quality_preset
+            .map_or(self.quality, |preset| preset.quality(output_format))
+    }
+
+    /// Near-lossless level of WebP encoding, `QualityPreset::Lossless` standing for plain lossless
+    #[cfg(feature = "webp")]
+    fn near_lossless(&self) -> Option<u8> {
+        self.webp_near_lossless
+            .or_else(|| (self.quality_preset == Some(QualityPreset::Lossless)).then_some(100))
+    }
+
+    /// Encodes with `quality`, in range [0.0, 100.0], unless `raw_quality` overrides it
+    fn encode_with_quality(
+        &self,
+        video_frame: &VideoFrame,
+        output_format: OutputFormat,
+        quality: f32,
+    ) -> Result<OutputContainer, ThumbnailerError> {
+        #[cfg(any(feature = "webp", feature = "jpeg"))]
+        let native_quality = self
+            .raw_quality
+            .or_else(|| output_format.native_quality(quality))
+            .unwrap_or_default();
+
+        let mut container = match output_format {
+            #[cfg(feature = "webp")]
+            OutputFormat::Webp => encode_webp(
+                video_frame,
+                native_quality,
+                self.near_lossless(),
+                self.dither,
+            ),
+            #[cfg(feature = "png")]
+            OutputFormat::Png => encode_png(video_frame),
+            OutputFormat::Raw => Ok(pack_raw(
+                video_frame,
+                self.raw_channel_order,
+                self.raw_row_order,
+            )),
+            #[cfg(feature = "jpeg")]
+            OutputFormat::Jpeg => encode_jpeg(video_frame, native_quality, self.jpeg_progressive),
+        }?;
+        container.quality = self.is_lossy(output_format).then_some(quality);
+        Ok(container)
+    }
+
+    /// Whether `quality` trades the size of `output_format` outputs for fidelity
+    fn is_lossy(&self, output_format: OutputFormat) -> bool {
+        #[cfg(feature = "webp")]
+        if output_format == OutputFormat::Webp && self.near_lossless().is_some() {
+            return false;
+        }
+        self.raw_quality.is_none() && output_format.native_quality(self.quality).is_some()
+    }
+
+    /// Binary searches the highest quality below the configured one whose output fits in
+    /// `max_bytes`, to a precision of less than one quality point
+    fn encode_within(
+        &self,
+        video_frame: &VideoFrame,
+        output_format: OutputFormat,
+        max_bytes: usize,
+    ) -> Result<OutputContainer, ThumbnailerError> {
+        let mut best = self.encode_with_quality(video_frame, output_format, 0.0)?;
+        if best.bytes.len() > max_bytes {
+            return Err(ThumbnailerError::OutputTooLarge {
+                size: best.bytes.len(),
+                max_bytes,
+            });
+        }
+
+        let (mut low, mut high) = (0.0, self.quality_for(output_format));
+        for _ in 0..TARGET_SIZE_STEPS {
+            let quality = (low + high) / 2.0;
+            let container = self.encode_with_quality(video_frame, output_format, quality)?;
+            if container.bytes.len() <= max_bytes {
+                low = quality;
+                best = container;
+            } else {
+                high = quality;
+            }
+        }
+        Ok(best)
+    }
+}
+
+#[cfg(all(test, feature = "webp"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_webp_rejects_empty_frames() {
+        let video_frame = VideoFrame::default();
+        assert!(matches!(
+            encode_webp(&video_frame, 80.0, None, false),
+            Err(ThumbnailerError::WebpEncodingError(_))
+        ));
+    }
+
+    #[test]
+    fn encode_webp_output() {
+        let video_frame = VideoFrame {
+            width: 4,
+            height: 2,
+            line_size: 12,
+            data: vec![128; 24],
+            ..Default::default()
+        };
+        let container = encode_webp(&video_frame, 80.0, None, false).unwrap();
+        assert_eq!(&container.bytes[..4], b"RIFF");
+        assert_eq!(&container.bytes[8..12], b"WEBP");
+    }
+
+    #[test]
+    fn pack_raw_drops_row_padding() {
+        let video_frame = VideoFrame {
+            width: 1,
+            height: 2,
+            line_size: 4,
+            data: vec![1, 2, 3, 0, 4, 5, 6, 0],
+            ..Default::default()
+        };
+        let container = pack_raw(&video_frame, ChannelOrder::Rgb, RowOrder::TopDown);
+        assert_eq!(container.bytes, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(container.format, OutputFormat::Raw);
+    }
+
+    #[test]
+    fn pack_raw_reorders_channels_and_rows() {
+        let video_frame = VideoFrame {
+            width: 1,
+            height: 2,
+            line_size: 4,
+            data: vec![1, 2, 3, 0, 4, 5, 6, 0],
+            ..Default::default()
+        };
+        let container = pack_raw(&video_frame, ChannelOrder::Bgra, RowOrder::BottomUp);
+        assert_eq!(container.bytes, vec![6, 5, 4, 255, 3, 2, 1, 255]);
+    }
+
+    #[test]
+    fn read_range_stops_at_input_end() {
+        let input = RangeInput::from(&[0, 1, 2, 3, 4][..]);
+        assert_eq!(input.clone().read_range(1, 3).unwrap(), vec![1, 2]);
+        assert_eq!(input.read_range(3, 10).unwrap(), vec![3, 4]);
+    }
+
+    #[tokio::test]
+    async fn process_stdin_requires_first_keyframe() {
+        let thumbnailer = ThumbnailerBuilder::new().build();
+        assert!(matches!(
+            thumbnailer.process_stdin(OutputFormat::Webp).await,
+            Err(ThumbnailerError::NonSeekableInput)
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn process_pipe_reads_piped_video() {
+        use std::os::unix::io::AsRawFd;
+        use std::process::{Command, Stdio};
+
+        let mut cat = Command::new("cat")
+            .arg(Path::new("samples").join("video_01.mp4"))
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let fd = cat.stdout.as_ref().unwrap().as_raw_fd();
+
+        let thumbnailer = ThumbnailerBuilder::new()
+            .frame_selection(FrameSelection::FirstKeyframe)
+            .build();
+        let container = thumbnailer
+            .process_pipe(fd, OutputFormat::Webp)
+            .await
+            .unwrap();
+        assert_eq!(&container.bytes[..4], b"RIFF");
+        drop(cat.stdout.take());
+        cat.wait().unwrap();
     }
 }
@@ -2,6 +2,12 @@ use crate::error::ThumbnailerError;
 use std::ffi::CString;
 use std::path::Path;
 
+#[cfg(feature = "fs")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "fs")]
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
 pub(crate) fn from_path(path: impl AsRef<Path>) -> Result<CString, ThumbnailerError> {
     #[cfg(unix)]
     {
@@ -28,3 +34,154 @@ pub(crate) fn from_path(path: impl AsRef<Path>) -> Result<CString, ThumbnailerEr
         .map_err(|_| ThumbnailerError::PathConversion(path.as_ref().to_path_buf()))
     }
 }
+
+/// Writes `bytes` to a temporary file in `temp_dir`, or next to `path`, then renames it to `path`,
+/// so that readers see either the previous file or the complete new one, never a truncated one.
+/// Renaming fails across filesystems, in which case the temporary file is copied next to `path`
+/// first, then renamed from there
+#[cfg(feature = "fs")]
+pub(crate) fn write_atomically(
+    path: &Path,
+    bytes: &[u8],
+    temp_dir: Option<&Path>,
+) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let temp_path = temp_path(path, temp_dir)?;
+    let result = std::fs::File::create(&temp_path).and_then(|mut file| {
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        match std::fs::rename(&temp_path, path) {
+            Err(_) if temp_dir.is_some() => {
+                copy_atomically(&temp_path, path).and_then(|_| std::fs::remove_file(&temp_path))
+            }
+            result => result,
+        }
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Copies `source` to a temporary file next to `path`, then renames it to `path`, as a rename
+/// can't be done across filesystems
+#[cfg(feature = "fs")]
+fn copy_atomically(source: &Path, path: &Path) -> std::io::Result<()> {
+    let temp_path = temp_path(path, None)?;
+    let result = std::fs::copy(source, &temp_path)
+        .and_then(|_| std::fs::File::open(&temp_path)?.sync_all())
+        .and_then(|_| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Unique hidden path for a temporary file in `temp_dir`, or next to `path`, named after `path`
+#[cfg(feature = "fs")]
+fn temp_path(path: &Path, temp_dir: Option<&Path>) -> std::io::Result<std::path::PathBuf> {
+    let file_name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "path has no file name")
+    })?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(
+        ".{}-{}.tmp",
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    Ok(match temp_dir {
+        Some(temp_dir) => temp_dir.join(temp_name),
+        None => path.with_file_name(temp_name),
+    })
+}
+
+/// Whether an I/O operation failing with `error` may succeed if simply retried, e.g. after
+/// `EAGAIN` on network filesystems
+#[cfg(feature = "fs")]
+pub(crate) fn is_transient(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::WouldBlock
+            | std::io::ErrorKind::Interrupted
+            | std::io::ErrorKind::TimedOut
+    )
+}
+
+/// Standard base64 encoding, with padding
+pub(crate) fn to_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (index, &byte)| {
+                group | (byte as u32) << (16 - 8 * index)
+            });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn write_atomically_uses_temp_dir() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = output_dir.path().join("thumbnail.webp");
+
+        write_atomically(&path, b"thumbnail", Some(temp_dir.path())).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"thumbnail");
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+        assert_eq!(std::fs::read_dir(output_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn copy_atomically_replaces_through_a_sibling() {
+        // The fallback of `write_atomically` when the temporary directory is on another
+        // filesystem than the output
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("source.tmp");
+        std::fs::write(&source, b"thumbnail").unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let path = output_dir.path().join("thumbnail.webp");
+        std::fs::write(&path, b"previous").unwrap();
+
+        copy_atomically(&source, &path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"thumbnail");
+        assert_eq!(std::fs::read_dir(output_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "fs")]
+    fn only_transient_errors_are_retried() {
+        use std::io::{Error, ErrorKind};
+
+        assert!(is_transient(&Error::from(ErrorKind::WouldBlock)));
+        assert!(!is_transient(&Error::from(ErrorKind::PermissionDenied)));
+    }
+
+    #[test]
+    fn to_base64_padding() {
+        assert_eq!(to_base64(b""), "");
+        assert_eq!(to_base64(b"f"), "Zg==");
+        assert_eq!(to_base64(b"fo"), "Zm8=");
+        assert_eq!(to_base64(b"foo"), "Zm9v");
+        assert_eq!(to_base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(to_base64(&[0xfb, 0xff]), "+/8=");
+    }
+}
@@ -1,5 +1,6 @@
-use crate::error::FfmpegError;
+use crate::{error::FfmpegError, DecodeStats};
 use ffmpeg_sys_next::{av_frame_alloc, av_frame_free, AVFrame};
+use std::{path::PathBuf, time::Duration};
 
 #[derive(Debug)]
 pub enum FrameSource {
@@ -7,15 +8,160 @@ pub enum FrameSource {
     Metadata,
 }
 
+/// Orientation of the video as displayed, once the sample aspect ratio and rotation are applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Landscape,
+    Portrait,
+    Square,
+}
+
+impl Orientation {
+    pub(crate) fn from_dimensions(width: u32, height: u32) -> Self {
+        match width.cmp(&height) {
+            std::cmp::Ordering::Greater => Orientation::Landscape,
+            std::cmp::Ordering::Less => Orientation::Portrait,
+            std::cmp::Ordering::Equal => Orientation::Square,
+        }
+    }
+
+    pub fn is_portrait(&self) -> bool {
+        *self == Orientation::Portrait
+    }
+}
+
+/// Origin of a thumbnail, embedded in the encoded thumbnail along with the crate version, set
+/// only with `ThumbnailerBuilder::embed_metadata`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMetadata {
+    /// Path of the input, `None` for inputs read from memory
+    pub source_path: Option<PathBuf>,
+    /// Position of the frame in the video stream, when known
+    pub seek_time: Option<Duration>,
+}
+
 #[derive(Debug, Default)]
 pub struct VideoFrame {
     pub width: u32,
     pub height: u32,
     pub line_size: u32,
     pub data: Vec<u8>,
+    /// Width of the source as decoded, see `display_dimensions` for the displayed one
     pub source_width: u32,
     pub source_height: u32,
+    /// Width and height of the source as decoded, before any crop, sample aspect ratio or
+    /// rotation, same as `source_width` and `source_height`
+    pub coded_dimensions: (u32, u32),
+    /// Width and height of the source as displayed, once cropped and with its sample aspect ratio
+    /// and rotation applied
+    pub display_dimensions: (u32, u32),
     pub source: Option<FrameSource>,
+    /// Position of the frame in the video stream, when known and not from embedded metadata
+    pub timestamp: Option<Duration>,
+    pub orientation: Option<Orientation>,
+    /// Whether the frame was scaled up from the source, as displayed once cropped and with its
+    /// sample aspect ratio and rotation applied
+    pub was_upscaled: bool,
+    /// Exif orientation to write in the encoded thumbnail, set only when the rotation is left to
+    /// viewers with `RotationHandling::ExifTag`
+    pub exif_orientation: Option<u16>,
+    /// FFmpeg name of the codec of the source stream, e.g. `h264`
+    pub source_codec: String,
+    /// FFmpeg name of the pixel format of the source stream, e.g. `yuv420p`
+    pub source_pixel_format: String,
+    pub decode_stats: DecodeStats,
+    /// ICC profile of the source, to embed in the encoded thumbnail, set only with
+    /// `ThumbnailerBuilder::preserve_icc`
+    pub icc_profile: Option<Vec<u8>>,
+    /// Origin of the frame, to embed in the encoded thumbnail
+    pub source_metadata: Option<SourceMetadata>,
+    /// 64-bit perceptual hash of the frame, before any color adjustment or film strip, set only
+    /// with `ThumbnailerBuilder::with_phash`
+    #[cfg(feature = "phash")]
+    pub phash: Option<u64>,
+    /// 64-bit average hash of the frame, before any color adjustment or film strip, set only
+    /// with `ThumbnailerBuilder::with_ahash`
+    #[cfg(feature = "phash")]
+    pub ahash: Option<u64>,
+}
+
+impl VideoFrame {
+    /// Mean luminance of the RGB24 pixels, using the BT.601 weights, in the range [0.0, 255.0]
+    pub(crate) fn mean_luminance(&self) -> f64 {
+        let row_length = self.width as usize * 3;
+        let mut sum = 0u64;
+        for row in self
+            .data
+            .chunks(self.line_size as usize)
+            .take(self.height as usize)
+        {
+            for pixel in row[..row_length.min(row.len())].chunks_exact(3) {
+                sum += 299 * pixel[0] as u64 + 587 * pixel[1] as u64 + 114 * pixel[2] as u64;
+            }
+        }
+
+        let pixel_count = self.width as u64 * self.height as u64;
+        if pixel_count == 0 {
+            return 0.0;
+        }
+        sum as f64 / 1000.0 / pixel_count as f64
+    }
+
+    /// Number of RGB24 pixels at each of the 256 luminance levels, using the BT.601 weights
+    pub(crate) fn luminance_histogram(&self) -> [u64; 256] {
+        let row_length = self.width as usize * 3;
+        let mut histogram = [0; 256];
+        for row in self
+            .data
+            .chunks(self.line_size as usize)
+            .take(self.height as usize)
+        {
+            for pixel in row[..row_length.min(row.len())].chunks_exact(3) {
+                let luminance =
+                    (299 * pixel[0] as u32 + 587 * pixel[1] as u32 + 114 * pixel[2] as u32 + 500)
+                        / 1000;
+                histogram[luminance as usize] += 1;
+            }
+        }
+        histogram
+    }
+
+    /// Copy of the RGB24 pixels as an image of the `image` crate, without the row padding, to
+    /// be transformed or encoded to any of its formats
+    #[cfg(feature = "image")]
+    pub fn to_dynamic_image(&self) -> image::DynamicImage {
+        let row_length = self.width as usize * 3;
+        let mut pixels = Vec::with_capacity(row_length * self.height as usize);
+        for row in self
+            .data
+            .chunks(self.line_size as usize)
+            .take(self.height as usize)
+        {
+            pixels.extend_from_slice(&row[..row_length.min(row.len())]);
+        }
+        pixels.resize(row_length * self.height as usize, 0);
+
+        let image = image::RgbImage::from_raw(self.width, self.height, pixels)
+            .expect("the buffer holds every pixel");
+        image::DynamicImage::ImageRgb8(image)
+    }
+}
+
+/// A single plane of a planar YUV frame, `stride` being the length in bytes of each row
+#[derive(Debug, Default)]
+pub struct YuvPlane {
+    pub data: Vec<u8>,
+    pub stride: u32,
+}
+
+/// Raw planar YUV420 frame, with the chroma planes subsampled by 2 in both directions
+#[derive(Debug, Default)]
+pub struct YuvFrame {
+    pub width: u32,
+    pub height: u32,
+    pub y: YuvPlane,
+    pub u: YuvPlane,
+    pub v: YuvPlane,
 }
 
 pub(crate) struct FfmpegFrame {